@@ -0,0 +1,30 @@
+// `cargo bench --bench score_distribution`, the time it takes to build the score distribution
+// of every 5* main stat for a fribbels scorer, which the inventory and farming reports do for
+// every character
+
+use std::time::{Duration, Instant};
+
+use est_tbp::{FribbelsScorer, Relic, RelicSlot, RelicStat};
+
+const PASSES: usize = 5;
+
+fn main() {
+    use RelicStat::*;
+    let scorer = FribbelsScorer::new([(CritRate, 1.0), (CritDmg, 1.0), (Spd, 1.0), (AtkPercent, 0.75)].into());
+    let relics = RelicSlot::all()
+        .flat_map(|slot| slot.mains().map(|main| Relic::new(5, slot, main)).collect::<Vec<_>>())
+        .collect::<Vec<_>>();
+
+    let mut times = (0..PASSES)
+        .map(|_| {
+            let start = Instant::now();
+            for relic in &relics {
+                let distribution = relic.score_distribution(&scorer).expect("mains are of their slot");
+                std::hint::black_box(distribution);
+            }
+            start.elapsed()
+        })
+        .collect::<Vec<Duration>>();
+    times.sort();
+    println!("{} distributions: median {:?} per pass, fastest {:?}", relics.len(), times[PASSES / 2], times[0]);
+}
@@ -1,18 +1,18 @@
-use est_tbp::{Relic, /* ConditionalRelicProbabilityCalculator,  */RelicSlot, RelicStat};
+use est_tbp::{DropModel, FarmingEstimate, InvalidRelicError, LinearScore, Relic, /* ConditionalRelicProbabilityCalculator,  */RelicSlot, RelicStat, ScoreDistribution};
 
-fn main() {
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let drop_model = DropModel::from_args(std::env::args())?;
     let relic = Relic::new(5, RelicSlot::Head, RelicStat::Hp);
+    let crit_rolls = LinearScore::roll_weights([(RelicStat::CritRate, 1.0), (RelicStat::CritDmg, 1.0)].into());
+    let distribution = relic.score_distribution(&crit_rolls)?;
+
     for i in 1..=7 {
-        calculate(&relic, i);
+        calculate(&relic, &distribution, &drop_model, i)?;
     }
+    Ok(())
 }
 
-fn calculate(relic: &Relic, crit_rolls: usize) {
-    let filter = |r: &Relic| r.subs.iter()
-        .filter(|r| matches!(r, RelicStat::CritRate | RelicStat::CritDmg))
-        .count()
-        >= crit_rolls;
-
+fn calculate(relic: &Relic, distribution: &ScoreDistribution, drop_model: &DropModel, crit_rolls: usize) -> Result<(), InvalidRelicError> {
     let crit_rolls = crit_rolls as f64;
 
     println!("=====================================================");
@@ -21,16 +21,19 @@ fn calculate(relic: &Relic, crit_rolls: usize) {
         crit_rolls * 0.8 * 6.48, crit_rolls * 6.48
     );
 
-    let p_main = relic.p_main();
-    let p_sub = relic.filtered_p_sub(filter);
+    let p_main = relic.p_main()?;
+    let p_sub = distribution.p_at_least(crit_rolls);
     let p = p_main * p_sub;
 
-    let est_relic_count = 1.0 / p;
-    let tbp_per_relic = 40.0 / 2.1;
-    let est_tbp = est_relic_count * tbp_per_relic;
+    let estimate = FarmingEstimate::new(p, drop_model.clone());
 
     println!("   p_main   = {:>6.3}%   (1/{:.1})", p_main * 100.0, 1.0 / p_main);
     println!("   p_sub    = {:>6.3}%   (1/{:.1})", p_sub * 100.0, 1.0 / p_sub);
     println!("   p        = {:>6.3}%   (1/{:.1})", p * 100.0, 1.0 / p);
-    println!("   est. tbp =  {:>6.0}   ({:.1} days)", est_tbp, est_tbp / 240.0);
-}
\ No newline at end of file
+    println!("   est. tbp =  {:>6.0}   ({:.1} days)", estimate.expected_tbp(), estimate.expected_days());
+    for q in [0.5, 0.9, 0.99] {
+        println!("   p{:<2} tbp  =  {:>6.0}   ({:.1} days)", q * 100.0, estimate.tbp_quantile(q), estimate.days_quantile(q));
+    }
+    println!("   2 weeks  = {:>6.3}%", estimate.p_within_days(14.0) * 100.0);
+    Ok(())
+}
@@ -1,20 +1,134 @@
 use std::collections::HashMap;
+use std::path::PathBuf;
+
+use serde::Serialize;
+use serde_json::Value;
+
+use est_tbp::{parse_base_stats, parse_weights, Build, DropModel, FarmingEstimate, FribbelsSave, FribbelsScorer, Grade, Imported, Inventory, InventoryAnalysis, PercentileRank, Relic, RelicScorer, RelicStat, SaveCharacter, ScoreDistribution, Swap, FarmingTarget, RelicSet};
+
+const USAGE: &str = "\
+usage: fribbels <save.json> [--inventory] [--swaps] [--farming] [options]
+
+without a mode flag, the relics each character has equipped are scored and ranked. the modes
+are flags rather than subcommands, any of them can be given together:
+  --inventory                look at every relic nobody wears instead of the equipped ones:
+                             upgrades, relics worth leveling and relics safe to salvage
+  --swaps                    look for equipped relics that do more for another character
+                             than for their owner, taking the optimizer tab's main stat filters
+  --farming                  rank domains and slots by the score their drops add per TBP
+                             across every character, for the sets each character wears
+
+options:
+  --character <name or id>   only report this character, can be given more than once
+  --weights <sources>        where to take stat weights from, first one that has them wins,
+                             comma separated from save, defaults, form (default: save,defaults,form)
+  --base-stats <file>        base HP, ATK and DEF by character id from the optimizer's character
+                             data, flat stats score nothing for characters without them
+  --drop-model <spec>        see DropModel, e.g. mult=2,fuel=1
+  --format <text|json>       output format (default: text)
+  --worn-sets                only give --inventory and --swaps relics of the sets a character
+                             wears, good relics of other sets then show as safe to salvage
+  --priority <name or id>=<weight>
+                             weight of a character's gains in --farming (default 1, 0 to leave
+                             them out), can be given more than once
+  --no-pause                 don't wait for enter before exiting";
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+enum WeightSource {
+    /// Relic scorer overrides in the save
+    Save,
+    /// defaultStatWeights.json
+    Defaults,
+    /// Optimizer tab of the character
+    Form,
+}
 
-use std::fs::File;
-use itertools::Itertools;
+#[derive(Clone, Copy, PartialEq, Debug)]
+enum Format {
+    Text,
+    Json,
+}
 
-use serde_json::{Map, Value};
+struct Args {
+    save: PathBuf,
+    characters: Vec<String>,
+    weight_sources: Vec<WeightSource>,
+    drop_model: DropModel,
+    format: Format,
+    inventory: bool,
+    swaps: bool,
+    farming: bool,
+    priorities: Vec<(String, f64)>,
+    worn_sets: bool,
+    base_stats: Option<PathBuf>,
+    pause: bool,
+}
 
-use est_tbp::{Relic, RelicSlot, RelicStat};
+#[derive(Serialize)]
+struct Report {
+    warnings: Vec<String>,
+    characters: Vec<CharacterReport>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    inventory: Option<InventoryReport>,
+}
 
-fn main() -> Result<(), Box<dyn std::error::Error>> {
-    let input = std::env::args().nth(1).expect("path as first arg");
-    let input = std::path::Path::new(&input);
-    assert!(input.exists());
+#[derive(Serialize)]
+struct CharacterReport {
+    id: u32,
+    name: Option<&'static str>,
+    weights: Option<HashMap<RelicStat, f64>>,
+    relics: Vec<RelicReport>,
+    warnings: Vec<String>,
+}
 
-    let save: Value = serde_json::from_reader(File::open(input)?)?;
+#[derive(Serialize)]
+struct RelicReport {
+    relic: Relic,
+    score: f64,
+    grade: Grade,
+    rank: PercentileRank,
+    expected_days: f64,
+    p90_days: f64,
+    forecast: Option<Forecast>,
+}
 
-    let mut missing_weights = false;
+/// Every relic, looked at across the characters that have weights
+#[derive(Serialize)]
+struct InventoryReport {
+    /// Names of the characters, `RelicUse::build` and the swaps index into it
+    builds: Vec<String>,
+    relics: HashMap<String, Relic>,
+    #[serde(flatten)]
+    analysis: Option<InventoryAnalysis>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    swaps: Option<Vec<Swap>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    farming: Option<Vec<FarmingTarget>>,
+}
+
+/// Where a relic that isn't fully leveled is headed
+#[derive(Serialize)]
+struct Forecast {
+    mean: f64,
+    top_10_score: f64,
+    p_top_10: f64,
+}
+
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let args = match parse_args(std::env::args().skip(1)) {
+        Ok(Some(args)) => args,
+        Ok(None) => {
+            println!("{USAGE}");
+            return Ok(());
+        }
+        Err(e) => {
+            eprintln!("error: {e}\n\n{USAGE}");
+            std::process::exit(2);
+        }
+    };
+
+    let save = FribbelsSave::from_json(&std::fs::read_to_string(&args.save)
+        .map_err(|e| format!("can't read {}: {e}", args.save.display()))?)?;
 
     /*
     Object.values(DB.getMetadata().characters).reduce((acc, cur) => {
@@ -23,183 +137,383 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
     }, {})
     */
     let default_weights_str = include_str!("../defaultStatWeights.json");
-    let default_weights: Value = serde_json::from_str(default_weights_str)?;
+    let default_weights: HashMap<String, HashMap<String, Value>> = serde_json::from_str(default_weights_str)?;
 
-    let relics = {
-        let mut relics = HashMap::new();
-        for relic in save["relics"].as_array().unwrap() {
-            let relic = relic.as_object().unwrap();
-            relics.insert(relic["id"].as_str().unwrap().to_string(), relic);
-        }
-        relics
+    /*
+    Object.values(DB.getMetadata().characters).reduce((acc, cur) => {
+      acc[cur.id] = cur.stats
+      return acc
+    }, {})
+    */
+    let base_stats = match &args.base_stats {
+        Some(path) => parse_base_stats(&std::fs::read_to_string(path)
+            .map_err(|e| format!("can't read {}: {e}", path.display()))?)?,
+        None => HashMap::new(),
     };
 
-    for char in save["characters"].as_array().unwrap() {
-        let char_id = char["id"].as_str().unwrap().parse().unwrap();
-        let char_name_opt = parse_char_id(char_id);
-        if char_name_opt.is_none() {
-            println!("{} ---------------", char_id);
-        } else {
-            println!("{} ---------------", char_name_opt.unwrap());
+    let relics = save.relics();
+    let mut report = Report { warnings: relics.warnings, characters: vec![], inventory: None };
+    let relics = relics.value;
+    let mut builds = vec![];
+    let mut farming_builds = vec![];
+
+    for char in &save.characters {
+        let Some(char_id) = char.id() else {
+            report.warnings.push(format!("skipped character with id {:?}", char.id));
+            continue;
+        };
+        if !args.characters.is_empty() && !args.characters.iter().any(|c| matches_character(c, char_id)) {
+            continue;
         }
 
-        let mut weights_opt = parse_weights_from_save(&save, char_id);
-        if weights_opt.is_none() {weights_opt = parse_default_weights(&default_weights, char_id)}
-        if weights_opt.is_none() {weights_opt = parse_optimizer_weights(&save, char_id)}
-        if weights_opt.is_none() {
-          if !missing_weights {
-            println!("No weights available for {}.", char_name_opt.unwrap_or(&format!("character with id {char_id}")));
-            println!("A temporary fix is to set the desired weights for the character in the optimiser tab and then launch an optimiser run.");
-            println!("For a long term fix either update the source code yourself (fribbels.rs) or contact the developer");
-            missing_weights = true;
-          }
-          continue;
+        let mut character = CharacterReport {
+            id: char_id,
+            name: parse_char_id(char_id),
+            weights: None,
+            relics: vec![],
+            warnings: vec![],
+        };
+
+        let weights = args.weight_sources.iter()
+            .find_map(|source| weights_from(*source, &save, char, &default_weights));
+        if let Some(weights) = weights {
+            character.warnings.extend(weights.warnings);
+            let flat = base_stats.get(&char_id).copied();
+            let new_scorer = || {
+                let scorer = FribbelsScorer::new(weights.value.clone());
+                match flat {
+                    Some((hp, atk, def)) => scorer.base_stats(hp, atk, def),
+                    None => scorer,
+                }
+            };
+            let scorer = new_scorer();
+            if flat.is_none() && [RelicStat::Hp, RelicStat::Atk, RelicStat::Def].iter().any(|stat| scorer.weight(*stat) > 0.0) {
+                character.warnings.push("no base stats, flat HP, ATK and DEF score nothing".to_string());
+            }
+            if args.inventory || args.swaps || args.farming {
+                let name = parse_char_id(char_id).map_or_else(|| char_id.to_string(), str::to_string);
+                let mains = char.form_mains();
+                character.warnings.extend(mains.warnings);
+                // sets aren't in the save, the ones worn are the best guess
+                let sets = char.equipped().filter_map(|id| relics.get(id)?.set).collect::<Vec<RelicSet>>();
+                let priority = args.priorities.iter()
+                    .rfind(|(name, _)| matches_character(name, char_id))
+                    .map_or(1.0, |(_, priority)| *priority);
+                let build = |scorer, sets| mains.value.clone().into_iter()
+                    .fold(Build::new(name.clone(), scorer, char.equipped().map(str::to_string)), |build, (slot, mains)| build.accept_mains(slot, mains))
+                    .accept_sets(sets)
+                    .priority(priority);
+                // any set can go to a character unless asked otherwise, or good relics of
+                // other sets would look safe to salvage
+                builds.push(build(scorer, if args.worn_sets { sets.clone() } else { vec![] }));
+                farming_builds.push(build(new_scorer(), sets));
+            } else {
+                character.relics = report_relics(char, &relics, &scorer, &args.drop_model, &mut character.warnings);
+            }
+            character.weights = Some(weights.value);
         }
-        let weights = weights_opt.unwrap();
+        report.characters.push(character);
+    }
 
-        let equipped = char["equipped"].as_object().unwrap().values();
+    for name in args.characters.iter().filter(|c| !report.characters.iter().any(|r| matches_character(c, r.id))) {
+        report.warnings.push(format!("no character `{name}` in the save"));
+    }
 
-        if equipped.len() == 0 {
-          println!("");
-          continue;
+    if args.inventory || args.swaps || args.farming {
+        // imports that can't be a real relic would only give nonsense numbers
+        let mut valid = HashMap::new();
+        for (id, relic) in relics {
+            match relic.validate() {
+                Ok(()) => {
+                    valid.insert(id, relic);
+                }
+                Err(e) => report.warnings.push(format!("skipped invalid relic {relic}: {e}")),
+            }
         }
-
-        println!("weights: {weights:?}");
-
-        equipped
-            .filter_map(|id| relics.get(id.as_str().unwrap()))
-            .map(|relic| parse_relic(relic))
-            .for_each(|relic| {
-                let score = relic_score(&relic, &weights);
-
-                let p_main = relic.p_main();
-                let p_sub = relic.filtered_p_sub(|r: &_| relic_score(r, &weights) > score);
-                let p = p_main * p_sub;
-
-                let est_relic_count = 1.0 / p;
-                let tbp_per_relic = 40.0 / 2.1;
-                let est_tbp = est_relic_count * tbp_per_relic;
-
-                println!("     est. {:>6.1} days | {:>5.1} score | [{:>10?} {:?}] {}", est_tbp / 240.0, score, relic.slot, relic.main, format_subs(&relic));
-            });
-        println!();
+        let worn = save.characters.iter().flat_map(|c| c.equipped().map(str::to_string)).collect::<Vec<_>>();
+        let farming = args.farming.then(|| Inventory::new(valid.clone(), farming_builds)
+            .worn(worn.clone())
+            .farming_targets(&args.drop_model));
+        let inventory = Inventory::new(valid, builds).worn(worn);
+        if args.worn_sets && (args.inventory || args.swaps) {
+            report.warnings.push("--worn-sets: relics only go to characters that wear their set, good relics of other sets can show as safe to salvage".to_string());
+        }
+        let mut analysis = args.inventory.then(|| inventory.analyze());
+        // a relic only looks like junk if every character it could go to was looked at
+        let unweighted = report.characters.iter()
+            .filter(|c| c.weights.is_none())
+            .map(|c| c.name.map_or_else(|| c.id.to_string(), str::to_string))
+            .collect::<Vec<_>>();
+        if let Some(analysis) = analysis.as_mut().filter(|_| !unweighted.is_empty()) {
+            analysis.salvage.clear();
+            report.warnings.push(format!("left out relics safe to salvage, no weights for {}", unweighted.join(", ")));
+        }
+        report.inventory = Some(InventoryReport {
+            builds: inventory.builds().iter().map(|b| b.name.clone()).collect(),
+            analysis,
+            swaps: args.swaps.then(|| inventory.recommended_swaps()),
+            farming,
+            relics: inventory.relics().clone(),
+        });
     }
 
-    println!("press enter to close");
-    std::io::stdin().read_line(&mut String::new()).unwrap();
+    match args.format {
+        Format::Text => print_text(&report),
+        Format::Json => println!("{}", serde_json::to_string_pretty(&report)?),
+    }
 
+    if args.pause {
+        eprintln!("press enter to close");
+        std::io::stdin().read_line(&mut String::new())?;
+    }
     Ok(())
 }
 
-fn format_subs(r: &Relic) -> String {
-    r.subs.iter()
-        .counts()
-        .iter()
-        .map(|(k, v)| format!("{v}x {k:?}"))
-        .join(", ")
-}
+fn report_relics(
+    char: &SaveCharacter,
+    relics: &HashMap<String, Relic>,
+    scorer: &FribbelsScorer,
+    drop_model: &DropModel,
+    warnings: &mut Vec<String>,
+) -> Vec<RelicReport> {
+    let mut distributions: HashMap<(usize, RelicStat), ScoreDistribution> = HashMap::new();
+    let mut reports = vec![];
+
+    for relic in char.equipped().filter_map(|id| relics.get(id)) {
+        // imports that can't be a real relic would only give nonsense numbers
+        if let Err(e) = relic.validate() {
+            warnings.push(format!("skipped invalid relic {relic}: {e}"));
+            continue;
+        }
 
-fn parse_relic(relic: &Map<String, Value>) -> Relic {
-    Relic {
-        rarity: relic["grade"].as_i64().unwrap() as usize,
-        slot: parse_slot(relic["part"].as_str().unwrap()),
-        main: parse_stat(relic["main"]["stat"].as_str().unwrap()).unwrap(),
-        subs: relic["substats"].as_array().unwrap()
-            .iter()
-            .flat_map(|sub| {
-                let stat = parse_stat(sub["stat"].as_str().unwrap()).unwrap();
-                let num = sub["addedRolls"].as_i64().unwrap() as usize + 1;
-                std::iter::repeat(stat).take(num)
-            })
-            .collect(),
+        let score = scorer.score(relic);
+        let distribution = distributions.entry((relic.rarity, relic.main))
+            .or_insert_with(|| relic.score_distribution(scorer).expect("validated above"));
+        let rank = distribution.rank(score);
+        let p_main = relic.p_main().expect("validated above");
+        let estimate = FarmingEstimate::new(p_main * rank.above, drop_model.clone());
+
+        let forecast = (relic.level < relic.max_level()).then(|| {
+            let top_10_score = distribution.quantile(0.9);
+            let forecast = relic.forecast_distribution(scorer).expect("validated above");
+            Forecast { mean: forecast.mean(), top_10_score, p_top_10: forecast.p_at_least(top_10_score) }
+        });
+
+        reports.push(RelicReport {
+            relic: relic.clone(),
+            score,
+            grade: scorer.grade(relic),
+            rank,
+            expected_days: estimate.expected_days(),
+            p90_days: estimate.days_quantile(0.9),
+            forecast,
+        });
     }
+    reports
 }
 
-fn parse_optimizer_weights(save: &Value, id: u32) -> Option<HashMap<RelicStat, f64>> {
-  let characters = save["characters"].as_array()?;
-  let character = characters.into_iter().find(|&x| {
-    let character = x.as_object();
-    if character.is_none() {return false;}
-    id == character.unwrap()["id"]
-  })?;
-  let pairs = character["form"]["weights"].as_object()?;
-  Some(parse_pairs(pairs))
-}
-
-fn parse_weights_from_save(save: &Value, id: u32) -> Option<HashMap<RelicStat, f64>> {
-    let pairs = save["scoringMetadataOverrides"][format!("{id}")]["stats"].as_object()?;
-    Some(parse_pairs(pairs))
-}
-
-fn parse_default_weights(defaults: &Value, id: u32) -> Option<HashMap<RelicStat, f64>> {
-  let pairs = defaults[format!("{id}")].as_object()?;
-  Some(parse_pairs(pairs))
-}
+fn print_text(report: &Report) {
+    for warning in &report.warnings {
+        println!("warning: {warning}");
+    }
 
-fn parse_pairs(pairs: &Map<String, Value>) -> HashMap<RelicStat, f64> {
-    let mut weights = HashMap::new();
-    for (k, v) in pairs {
-        let stat = parse_stat(k);
-        if let Some(stat) = stat {
-            let mut w = v.as_f64().unwrap();
+    let mut missing_weights = false;
+    for character in &report.characters {
+        if report.inventory.is_some() && character.weights.is_some() {
+            continue;
+        }
+        match character.name {
+            Some(name) => println!("{name} ---------------"),
+            None => println!("{} ---------------", character.id),
+        }
+        for warning in &character.warnings {
+            println!("warning: {warning}");
+        }
 
-            if matches!(stat, RelicStat::Atk | RelicStat::Def | RelicStat::Hp) {
-                w *= 0.4;
+        let Some(weights) = &character.weights else {
+            if !missing_weights {
+                println!("No weights available for {}.", character.name.unwrap_or(&format!("character with id {}", character.id)));
+                println!("A temporary fix is to set the desired weights for the character in the optimiser tab and then launch an optimiser run.");
+                println!("For a long term fix either update the source code yourself (fribbels.rs) or contact the developer");
+                missing_weights = true;
             }
+            continue;
+        };
+        if character.relics.is_empty() {
+            println!();
+            continue;
+        }
+        println!("weights: {weights:?}");
 
-            if matches!(stat, RelicStat::Spd) {
-              w *= 2.3 * 2.59 / (6.48*0.9); // hack to get more accurate speed scores
+        for r in &character.relics {
+            let relic = &r.relic;
+            println!(
+                "     est. {:>6.1} days (p90 {:>6.1}) | {:>5.1} score {:<3} | {:>10} | [{:>12} {}] {} ({})",
+                r.expected_days, r.p90_days, r.score, format!("{:?}", r.grade), r.rank.to_string(), relic.slot, relic.main, relic.format_subs(),
+                relic.set.map_or("unknown set".to_string(), |set| set.to_string())
+            );
+            if let Some(forecast) = &r.forecast {
+                println!(
+                    "          +{} -> +{}: {:.1} score on average, {:.0}% to end in the top 10% (>= {:.1})",
+                    relic.level, relic.max_level(), forecast.mean, forecast.p_top_10 * 100.0, forecast.top_10_score
+                );
             }
+        }
+        println!();
+    }
 
-            w *= 1000.0;
-            w = w.floor(); // loses some of the precision from above but otherwise its ugly :mad:
-            w /= 1000.0;
+    if let Some(inventory) = &report.inventory {
+        // salvage is left out when a character has no weights, see the warnings
+        print_inventory(inventory, report.characters.iter().all(|c| c.weights.is_some()));
+    }
+}
+
+fn print_inventory(inventory: &InventoryReport, salvage: bool) {
+    let describe = |id: &str| {
+        let relic = &inventory.relics[id];
+        format!(
+            "[{:>12} {} +{:<2}] {} ({})",
+            relic.slot, relic.main, relic.level, relic.format_subs(),
+            relic.set.map_or("unknown set".to_string(), |set| set.to_string())
+        )
+    };
+    if let Some(swaps) = &inventory.swaps {
+        println!("swaps ---------------");
+        for swap in swaps {
+            println!(
+                "     {:>+6.1} score | {} -> {} ({:+.1} / {:+.1}) | {}",
+                swap.gain(), inventory.builds[swap.from], inventory.builds[swap.to], swap.from_delta, swap.to_delta, describe(&swap.relic)
+            );
+            if let Some(back) = &swap.back {
+                println!("{:>40} {}", "in exchange for", describe(back));
+            }
+        }
+        println!();
+    }
 
-            // if w != 0.0 {
-            weights.insert(stat, w);
-            // }
+    if let Some(targets) = &inventory.farming {
+        println!("farming ---------------");
+        for target in targets {
+            let gains = target.gains.iter()
+                .map(|(build, gain)| format!("{} {:.1}", inventory.builds[*build], gain * 1000.0))
+                .collect::<Vec<_>>();
+            println!(
+                "     {:>6.1} score per 1000 TBP | {:>12} from {} / {} | {}",
+                target.gain_per_tbp() * 1000.0, target.slot, target.domain.sets[0], target.domain.sets[1], gains.join(", ")
+            );
         }
+        println!();
+    }
+
+    let Some(analysis) = &inventory.analysis else {
+        return;
+    };
+
+    println!("upgrades ---------------");
+    for (id, upgrade) in &analysis.upgrades {
+        println!(
+            "     {:>+6.1} score for {:<20} | {:>5.1} over {:>5.1} | {}",
+            upgrade.gain(), inventory.builds[upgrade.build], upgrade.score, upgrade.equipped_score, describe(id)
+        );
+    }
+    println!();
+
+    println!("worth leveling ---------------");
+    for (id, prospect) in &analysis.prospects {
+        println!(
+            "     {:>5.1}% to beat {:>5.1} for {:<20} | {}",
+            prospect.p_better * 100.0, prospect.equipped_score, inventory.builds[prospect.build], describe(id)
+        );
+    }
+    println!();
+
+    if !salvage {
+        return;
+    }
+    println!("safe to salvage ({}) ---------------", analysis.salvage.len());
+    for id in &analysis.salvage {
+        println!("     {}", describe(id));
     }
-    weights
+    println!();
 }
 
-fn parse_slot(s: &str) -> RelicSlot {
-    match s {
-        "PlanarSphere" => RelicSlot::Orb,
-        "Hands" => RelicSlot::Hands,
-        "Body" => RelicSlot::Body,
-        "LinkRope" => RelicSlot::Rope,
-        "Head" => RelicSlot::Head,
-        "Feet" => RelicSlot::Feet,
-        _ => panic!("unknown relic slot")
+fn weights_from(
+    source: WeightSource,
+    save: &FribbelsSave,
+    char: &SaveCharacter,
+    defaults: &HashMap<String, HashMap<String, Value>>,
+) -> Option<Imported<HashMap<RelicStat, f64>>> {
+    let id = char.id()?;
+    match source {
+        WeightSource::Save => save.scoring_weights(id),
+        WeightSource::Defaults => defaults.get(&id.to_string()).map(parse_weights),
+        WeightSource::Form => char.form_weights(),
     }
 }
 
-fn parse_stat(s: &str) -> Option<RelicStat> {
-    match s {
-        "HP" => Some(RelicStat::Hp),
-        "HP%" => Some(RelicStat::HpPercent),
-        "ATK" => Some(RelicStat::Atk),
-        "ATK%" => Some(RelicStat::AtkPercent),
-        "DEF" => Some(RelicStat::Def),
-        "DEF%" => Some(RelicStat::DefPercent),
-        "CRIT Rate" => Some(RelicStat::CritRate),
-        "CRIT DMG" => Some(RelicStat::CritDmg),
-        "Break Effect" => Some(RelicStat::BreakEffect),
-        "Effect Hit Rate" => Some(RelicStat::EffectHitRate),
-        "Energy Regeneration Rate" => Some(RelicStat::EnergyRegenRate),
-        "Fire DMG Boost" => Some(RelicStat::FireDmgBoost),
-        "Ice DMG Boost" => Some(RelicStat::IceDmgBoost),
-        "Imaginary DMG Boost" => Some(RelicStat::ImaginaryDmgBoost),
-        "Lightning DMG Boost" => Some(RelicStat::LightningDmgBoost),
-        "Outgoing Healing Boost" => Some(RelicStat::HealingBoost),
-        "Physical DMG Boost" => Some(RelicStat::PhysDmgBoost),
-        "Quantum DMG Boost" => Some(RelicStat::QuantumDmgBoost),
-        "Effect RES" => Some(RelicStat::EffectRes),
-        "SPD" => Some(RelicStat::Spd),
-        "Wind DMG Boost" => Some(RelicStat::WindDmgBoost),
-        _ => None
+// by id, or by name ignoring case
+fn matches_character(name_or_id: &str, id: u32) -> bool {
+    name_or_id.parse() == Ok(id)
+        || parse_char_id(id).is_some_and(|name| name.eq_ignore_ascii_case(name_or_id.trim()))
+}
+
+// `None` when help was asked for
+fn parse_args(mut args: impl Iterator<Item=String>) -> Result<Option<Args>, String> {
+    let mut save = None;
+    let mut characters = vec![];
+    let mut weight_sources = vec![WeightSource::Save, WeightSource::Defaults, WeightSource::Form];
+    let mut drop_model = DropModel::default();
+    let mut format = Format::Text;
+    let mut inventory = false;
+    let mut swaps = false;
+    let mut farming = false;
+    let mut priorities = vec![];
+    let mut worn_sets = false;
+    let mut base_stats = None;
+    let mut pause = true;
+
+    while let Some(arg) = args.next() {
+        let mut value = || args.next().ok_or(format!("missing value for {arg}"));
+        match arg.as_str() {
+            "-h" | "--help" => return Ok(None),
+            "--character" => characters.push(value()?),
+            "--weights" => {
+                weight_sources = value()?.split(',')
+                    .map(|source| match source.trim() {
+                        "save" => Ok(WeightSource::Save),
+                        "defaults" => Ok(WeightSource::Defaults),
+                        "form" => Ok(WeightSource::Form),
+                        other => Err(format!("unknown weight source `{other}`")),
+                    })
+                    .collect::<Result<_, _>>()?;
+            }
+            "--drop-model" => drop_model = value()?.parse().map_err(|e| format!("{e}"))?,
+            "--format" => format = match value()?.as_str() {
+                "text" => Format::Text,
+                "json" => Format::Json,
+                other => return Err(format!("unknown format `{other}`")),
+            },
+            "--inventory" => inventory = true,
+            "--swaps" => swaps = true,
+            "--farming" => farming = true,
+            "--worn-sets" => worn_sets = true,
+            "--priority" => {
+                let value = value()?;
+                let (name, priority) = value.rsplit_once('=')
+                    .and_then(|(name, priority)| Some((name.to_string(), priority.trim().parse::<f64>().ok()?)))
+                    .ok_or(format!("expected <name or id>=<weight>, got `{value}`"))?;
+                priorities.push((name, priority));
+            }
+            "--base-stats" => base_stats = Some(PathBuf::from(value()?)),
+            "--no-pause" => pause = false,
+            flag if flag.starts_with("--") => return Err(format!("unknown option {flag}")),
+            _ if save.is_none() => save = Some(PathBuf::from(arg)),
+            _ => return Err(format!("unexpected argument {arg}")),
+        }
     }
+
+    let save = save.ok_or("missing the path of the save")?;
+    Ok(Some(Args { save, characters, weight_sources, drop_model, format, inventory, swaps, farming, priorities, worn_sets, base_stats, pause }))
 }
 
 // https://raw.githubusercontent.com/fribbels/hsr-optimizer/main/src/data/characters.json
@@ -286,9 +600,3 @@ fn parse_char_id(id: u32) -> Option<&'static str> {
         _ => None
     }
 }
-
-fn relic_score(relic: &Relic, weights: &HashMap<RelicStat, f64>) -> f64 {
-    relic.subs.iter()
-        .map(|r| weights.get(r).unwrap_or(&0f64) * 6.48 * 0.9)
-        .sum::<f64>()
-}
\ No newline at end of file
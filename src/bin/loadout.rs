@@ -1,75 +1,92 @@
 use std::collections::HashMap;
-use est_tbp::{Relic, ConditionalRelicProbabilityCalculator, RelicSlot, RelicStat};
 
-fn main() {
-    let mut weights = HashMap::new();
-    weights.insert(RelicStat::CritRate, 1.0);
-    weights.insert(RelicStat::CritDmg, 1.0);
-    weights.insert(RelicStat::Spd, 1.0);
-    weights.insert(RelicStat::AtkPercent, 0.75);
-    weights.insert(RelicStat::Atk, 0.25);
+use serde::Deserialize;
 
-    let loadout = {
-        use RelicStat::*;
-        use RelicSlot::*;
+use est_tbp::{Relic, CompletionEstimate, ConditionalRelicProbabilityCalculator, DropModel, FarmingEstimate, LinearScore, RelicStat, StatTargets};
 
-        vec![
-            Relic {
-                rarity: 5,
-                slot: Head,
-                main: Hp,
-                subs: vec![Def, AtkPercent, Spd, CritDmg, AtkPercent, Spd, Spd, Spd, CritDmg],
-            },
-            Relic {
-                rarity: 5,
-                slot: Hands,
-                main: Atk,
-                subs: vec![Def, AtkPercent, CritRate, CritDmg, CritRate, CritRate, Def, Def],
-            },
-            Relic {
-                rarity: 5,
-                slot: Body,
-                main: CritRate,
-                subs: vec![Hp, Atk, AtkPercent, BreakEffect, Atk, AtkPercent, AtkPercent, BreakEffect],
-            },
-            Relic {
-                rarity: 5,
-                slot: Feet,
-                main: Spd,
-                subs: vec![HpPercent, AtkPercent, EffectHitRate, EffectRes, AtkPercent, AtkPercent, AtkPercent, AtkPercent, AtkPercent],
-            },
-        ]
-    };
+// `--loadout <file>` in the same format as data/loadout.json
+#[derive(Deserialize)]
+struct Loadout {
+    calculator: ConditionalRelicProbabilityCalculator,
+    roll_weights: HashMap<RelicStat, f64>,
+    relics: Vec<Piece>,
+    /// Totals the whole build has to reach, see `StatTargets`
+    targets: Option<StatTargets>,
+}
 
-    let calculator = ConditionalRelicProbabilityCalculator::new()
-        .consider_set()
-        .consider_slot()
-        .consider_main();
+#[derive(Deserialize)]
+struct Piece {
+    #[serde(flatten)]
+    relic: Relic,
+    /// Score a new piece has to reach, more than the current one when left out
+    min_score: Option<f64>,
+    /// Main stats that will do instead of only the relic's own
+    #[serde(default)]
+    accepted_mains: Vec<RelicStat>,
+}
+
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let drop_model = DropModel::from_args(std::env::args())?;
+
+    let loadout: Loadout = match std::env::args().skip_while(|arg| arg != "--loadout").nth(1) {
+        Some(path) => serde_json::from_str(&std::fs::read_to_string(path)?)?,
+        None => serde_json::from_str(include_str!("../../data/loadout.json"))?,
+    };
+    let score = LinearScore::roll_weights(loadout.roll_weights);
+    let calculator = loadout.calculator;
+    let targets = loadout.targets;
+    let loadout = loadout.relics;
 
     let mut total_p = 1.0;
-    for relic in loadout {
-        let score = relic_score(&relic, &weights);
-        let p = calculator.calculate_for_relic(&relic, |r| relic_score(r, &weights) >= score);
+    let mut completion = CompletionEstimate::new(drop_model.clone());
+    for Piece { relic, min_score, accepted_mains } in &loadout {
+        relic.validate().map_err(|e| format!("{relic}: {e}"))?;
+        let calculator = match accepted_mains.is_empty() {
+            true => calculator.clone(),
+            false => calculator.clone().accept_mains(accepted_mains.iter().copied()),
+        };
+        let min_score = min_score.unwrap_or_else(|| score.score(relic));
+        let p = calculator.calculate_for_score(relic, &score, min_score)?;
         total_p *= 1.0 - p;
-        println!("{relic:?}");
-        print_tbp(p);
+        // completion counts drops, so the slot has to be part of it
+        let p_drop = calculator.clone().consider_slot().calculate_for_score(relic, &score, min_score)?;
+        completion = completion.piece(calculator.domain(relic), relic.slot, p_drop)?;
+        println!("{relic}, to reach {min_score:.2}");
+        print_tbp(p, &drop_model);
+
+        if let Some(targets) = &targets {
+            let others = loadout.iter().map(|piece| &piece.relic).filter(|other| other.slot != relic.slot);
+            let missing = targets.missing(others.clone());
+            let p = calculator.calculate_for_targets(relic, targets, others)?;
+            let missing = missing.iter().map(|(stat, value)| format!("{value:.1} {stat}")).collect::<Vec<_>>();
+            println!("to reach the stat targets in its place ({}):", if missing.is_empty() { "nothing missing".to_string() } else { missing.join(", ") });
+            print_tbp(p, &drop_model);
+        }
         println!();
     }
 
     println!("overall to improve a single piece:");
-    print_tbp(1.0 - total_p);
-}
+    print_tbp(1.0 - total_p, &drop_model);
+    println!();
 
-fn relic_score(relic: &Relic, weights: &HashMap<RelicStat, f64>) -> f64 {
-    relic.subs.iter()
-        .map(|r| weights.get(r).unwrap_or(&0f64))
-        .sum::<f64>()
+    // every piece has to drop, cavern and planar pieces from their own domains
+    println!("to get every piece:");
+    println!(
+        "{:.0} tbp ({:.1}d), median {:.0} tbp ({:.1}d), p90 {:.0} tbp ({:.1}d)",
+        completion.expected_tbp(), completion.expected_days(),
+        completion.tbp_quantile(0.5), completion.days_quantile(0.5),
+        completion.tbp_quantile(0.9), completion.days_quantile(0.9),
+    );
+    Ok(())
 }
 
-fn print_tbp(p: f64) {
+fn print_tbp(p: f64, drop_model: &DropModel) {
+    let estimate = FarmingEstimate::new(p, drop_model.clone());
     let percent = p * 100.0;
     let relics = 1.0 / p;
-    let tbp = relics * 40.0 / 2.1;
-    let days = tbp / 240.0;
-    println!("{percent:.4}% (1/{relics:.1}), {tbp:.0} tbp ({days:.1}d)");
-}
\ No newline at end of file
+    let tbp = estimate.expected_tbp();
+    let days = estimate.expected_days();
+    let p90_tbp = estimate.tbp_quantile(0.9);
+    let p90_days = estimate.days_quantile(0.9);
+    println!("{percent:.4}% (1/{relics:.1}), {tbp:.0} tbp ({days:.1}d), p90 {p90_tbp:.0} tbp ({p90_days:.1}d)");
+}
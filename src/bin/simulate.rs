@@ -0,0 +1,70 @@
+use est_tbp::{CrossCheck, DropModel, FarmingEstimate, LevelingCosts, LevelingPolicy, LinearScore, Relic, RelicSlot, RelicStat, Simulator};
+
+// cross checks the analytic results against the simulator, `--seed <n>` to change the seed
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let drop_model = DropModel::from_args(std::env::args())?;
+    let seed = std::env::args()
+        .skip_while(|arg| arg != "--seed")
+        .nth(1)
+        .and_then(|seed| seed.parse().ok())
+        .unwrap_or(0);
+    let mut simulator = Simulator::new(seed).drop_model(drop_model.clone());
+
+    let relic = Relic::new(5, RelicSlot::Head, RelicStat::Hp);
+    let crit_rolls = LinearScore::roll_weights([(RelicStat::CritRate, 1.0), (RelicStat::CritDmg, 1.0)].into());
+
+    println!("=====================================================");
+    println!("p_sub, {:?} {:?}", relic.slot, relic.main);
+    let check = simulator.check_p_sub(&relic, 200_000, |r| crit_rolls.score(r) >= 4.0)?;
+    print_check("filtered_p_sub, 4 crit rolls", &check);
+
+    let distribution = relic.score_distribution(&crit_rolls)?;
+    for i in 1..=7 {
+        let min_score = i as f64;
+        let check = CrossCheck {
+            analytic: distribution.p_at_least(min_score),
+            simulated: simulator.estimate_p_sub(&relic, 200_000, |r| crit_rolls.score(r) >= min_score),
+        };
+        print_check(&format!("score_distribution, {i} crit rolls"), &check);
+    }
+
+    println!("=====================================================");
+    println!("farming, {:?} {:?}, 3 crit rolls", relic.slot, relic.main);
+    let success = |r: &Relic| crit_rolls.score(r) >= 3.0;
+    let estimate = FarmingEstimate::new(relic.p_main()? * distribution.p_at_least(3.0), drop_model.clone());
+    let farming = simulator.estimate_farming(&relic, 10_000, |_| true, success);
+    print_check("tbp", &CrossCheck { analytic: estimate.expected_tbp(), simulated: farming.tbp });
+
+    // made up costs, high enough for the policy to feed relics early
+    let costs = LevelingCosts::free()
+        .exp_per_upgrade(5, vec![4000.0, 9000.0, 14500.0, 20500.0, 27500.0])
+        .fodder_exp(5, 1500.0)
+        .exp_refund(0.8)
+        .credits_per_exp(0.1)
+        .tbp_per_exp(1.0 / 400.0)
+        .tbp_per_credit(1.0 / 1000.0);
+    let policy = LevelingPolicy::optimize(&relic, &crit_rolls, 3.0, &drop_model, &costs)?;
+    let mut simulator = simulator.leveling_costs(costs);
+    let all = simulator.estimate_farming(&relic, 10_000, |_| true, success);
+    let optimal = simulator.estimate_farming(&relic, 10_000, |r| policy.keep_leveling(r).unwrap_or(true), success);
+    print_check(
+        "tbp incl. leveling, leveling everything",
+        &CrossCheck { analytic: policy.tbp_per_success_leveling_all(), simulated: all.total_tbp },
+    );
+    print_check(
+        "tbp incl. leveling, leveling policy",
+        &CrossCheck { analytic: policy.tbp_per_success(), simulated: optimal.total_tbp },
+    );
+    Ok(())
+}
+
+fn print_check(name: &str, check: &CrossCheck) {
+    let (low, high) = check.simulated.confidence_interval();
+    println!(
+        "   {name:<40} {:>10.5}   simulated {:>10.5} [{low:.5}, {high:.5}]   z = {:>5.2}{}",
+        check.analytic,
+        check.simulated.mean,
+        check.z_score(),
+        if check.agrees(3.0) { "" } else { "   !!" }
+    );
+}
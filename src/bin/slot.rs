@@ -1,8 +1,23 @@
 use std::collections::HashMap;
 
-use est_tbp::{Relic, RelicSlot, RelicStat};
+use est_tbp::{DropModel, FarmingEstimate, GameTables, InvalidRelicError, LevelingCosts, LevelingPolicy, LinearScore, Relic, RelicSlot, RelicStat};
+
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let drop_model = DropModel::from_args(std::env::args())?;
+
+    // `--tables <file>` to use other game tables than the embedded ones
+    let tables = match std::env::args().skip_while(|arg| arg != "--tables").nth(1) {
+        Some(path) => GameTables::from_json(&std::fs::read_to_string(path)?)?,
+        None => GameTables::embedded().clone(),
+    };
+
+    // `--leveling-costs <spec>` to also plan the leveling, there are no default costs
+    let costs = std::env::args()
+        .skip_while(|arg| arg != "--leveling-costs")
+        .nth(1)
+        .map(|spec| spec.parse::<LevelingCosts>())
+        .transpose()?;
 
-fn main() {
     let mut score_weights = HashMap::new();
     score_weights.insert(RelicStat::CritRate, 1.0);
     score_weights.insert(RelicStat::CritDmg, 1.0);
@@ -10,36 +25,57 @@ fn main() {
     score_weights.insert(RelicStat::AtkPercent, 0.75);
     score_weights.insert(RelicStat::Atk, 0.25);
 
-    let filter = |r: &_| relic_score(r, &score_weights) >= 6.0;
+    let score = LinearScore::roll_weights(score_weights);
+    let min_score = 6.0;
 
-    calculate(Relic::new(5, RelicSlot::Head, RelicStat::Hp), filter);
-    calculate(Relic::new(5, RelicSlot::Hands, RelicStat::Atk), filter);
-    calculate(Relic::new(5, RelicSlot::Body, RelicStat::CritRate), filter);
-    calculate(Relic::new(5, RelicSlot::Feet, RelicStat::Spd), filter);
-    calculate(Relic::new(5, RelicSlot::Orb, RelicStat::IceDmgBoost), filter);
-    calculate(Relic::new(5, RelicSlot::Rope, RelicStat::EnergyRegenRate), filter);
+    tables.scope(|| {
+        calculate(Relic::new(5, RelicSlot::Head, RelicStat::Hp), &score, min_score, &drop_model, costs.as_ref())?;
+        calculate(Relic::new(5, RelicSlot::Hands, RelicStat::Atk), &score, min_score, &drop_model, costs.as_ref())?;
+        calculate(Relic::new(5, RelicSlot::Body, RelicStat::CritRate), &score, min_score, &drop_model, costs.as_ref())?;
+        calculate(Relic::new(5, RelicSlot::Feet, RelicStat::Spd), &score, min_score, &drop_model, costs.as_ref())?;
+        calculate(Relic::new(5, RelicSlot::Orb, RelicStat::IceDmgBoost), &score, min_score, &drop_model, costs.as_ref())?;
+        calculate(Relic::new(5, RelicSlot::Rope, RelicStat::EnergyRegenRate), &score, min_score, &drop_model, costs.as_ref())
+    })?;
+    Ok(())
 }
 
-fn calculate(relic: Relic, filter: impl Fn(&Relic) -> bool) {
+fn calculate(
+    relic: Relic,
+    score: &LinearScore,
+    min_score: f64,
+    drop_model: &DropModel,
+    costs: Option<&LevelingCosts>,
+) -> Result<(), InvalidRelicError> {
     println!("=====================================================");
     println!("{relic:?}");
 
-    let p_main = relic.p_main();
-    let p_sub = relic.filtered_p_sub(filter);
+    let p_main = relic.p_main()?;
+    let p_sub = relic.score_distribution(score)?.p_at_least(min_score);
     let p = p_main * p_sub;
 
-    let est_relic_count = 1.0 / p;
-    let tbp_per_relic = 40.0 / 2.1;
-    let est_tbp = est_relic_count * tbp_per_relic;
+    let estimate = FarmingEstimate::new(p, drop_model.clone());
 
     println!("   p_main   = {:>6.3}%   (1/{:.1})", p_main * 100.0, 1.0 / p_main);
     println!("   p_sub    = {:>6.3}%   (1/{:.1})", p_sub * 100.0, 1.0 / p_sub);
     println!("   p        = {:>6.3}%   (1/{:.1})", p * 100.0, 1.0 / p);
-    println!("   est. tbp =  {:>6.0}   ({:.1} days)", est_tbp, est_tbp / 240.0);
-}
+    println!("   est. tbp =  {:>6.0}   ({:.1} days)", estimate.expected_tbp(), estimate.expected_days());
+    for q in [0.5, 0.9, 0.99] {
+        println!("   p{:<2} tbp  =  {:>6.0}   ({:.1} days)", q * 100.0, estimate.tbp_quantile(q), estimate.days_quantile(q));
+    }
+    println!("   2 weeks  = {:>6.3}%", estimate.p_within_days(14.0) * 100.0);
 
-fn relic_score(relic: &Relic, weights: &HashMap<RelicStat, f64>) -> f64 {
-    relic.subs.iter()
-        .map(|r| weights.get(r).unwrap_or(&0f64))
-        .sum::<f64>()
-}
\ No newline at end of file
+    // feeding hopeless relics early instead of leveling everything to +15
+    let Some(costs) = costs else { return Ok(()) };
+    let policy = LevelingPolicy::optimize(&relic, score, min_score, drop_model, costs)?;
+    println!(
+        "   leveled  =  {:>6.0}   tbp per success incl. leveling ({:.0} leveling everything)",
+        policy.tbp_per_success(), policy.tbp_per_success_leveling_all()
+    );
+    for step in policy.steps().iter().filter(|s| s.p_stopped > 0.0) {
+        println!(
+            "   feed +{:<2}  = {:>6.3}%   (keep the ones with >= {:.1}% left)",
+            step.level, step.p_stopped * 100.0, step.min_p_continue * 100.0
+        );
+    }
+    Ok(())
+}
@@ -0,0 +1,311 @@
+use std::fmt;
+
+use crate::{Domain, DropModel, RelicSlot, SetKind};
+
+// where a piece is farmed, its domain or any domain of the kind
+type FarmedIn = (Option<Domain>, SetKind);
+
+/// Distribution of the TBP spent until every piece of a build has dropped at least once
+///
+/// A drop can only be a success for one piece, so pieces should be different slots. Pieces
+/// farmed in the same domain share its drops, and domains are farmed one after another.
+#[derive(Clone, Debug)]
+pub struct CompletionEstimate {
+    // slot and probability of a drop being a success for each piece, by where it is farmed
+    groups: Vec<(FarmedIn, Vec<(RelicSlot, f64)>)>,
+    drop_model: DropModel,
+}
+
+impl CompletionEstimate {
+    pub fn new(drop_model: DropModel) -> Self {
+        Self { groups: vec![], drop_model }
+    }
+
+    /// A piece that drops with probability `p` per relic in `domain`, or in any domain of
+    /// its slot when the set doesn't matter. `p` is per drop, so it includes the chance of the
+    /// slot. A piece of any set is farmed along with a domain of its kind when there is one.
+    /// Fails for a `p` outside of [0, 1] or a slot that is already farmed in `domain`.
+    pub fn piece(mut self, domain: Option<Domain>, slot: RelicSlot, p: f64) -> Result<Self, InvalidPieceError> {
+        if !(0.0..=1.0).contains(&p) {
+            return Err(InvalidPieceError(format!("{slot} drops with probability {p}")));
+        }
+        let kind = if matches!(slot, RelicSlot::Orb | RelicSlot::Rope) { SetKind::Planar } else { SetKind::Cavern };
+        match self.groups.iter_mut().find(|(key, _)| *key == (domain, kind)) {
+            Some((_, pieces)) if pieces.iter().any(|(s, _)| *s == slot) => {
+                return Err(InvalidPieceError(format!("{slot} is already farmed in the same domain")));
+            }
+            Some((_, pieces)) => pieces.push((slot, p)),
+            None => self.groups.push(((domain, kind), vec![(slot, p)])),
+        }
+        Ok(self)
+    }
+
+    pub fn expected_runs(&self) -> f64 {
+        if self.groups.iter().any(|(_, pieces)| pieces.iter().any(|(_, p)| *p <= 0.0)) {
+            return f64::INFINITY;
+        }
+        self.farmed_groups().iter().map(|pieces| self.group_expected_runs(pieces)).sum()
+    }
+
+    pub fn expected_tbp(&self) -> f64 {
+        self.expected_runs() * self.drop_model.run_tbp()
+    }
+
+    pub fn expected_days(&self) -> f64 {
+        self.drop_model.days_for_tbp(self.expected_tbp())
+    }
+
+    /// Number of runs after which every piece has dropped with probability `q`. Exact for
+    /// a single domain, otherwise see [`Self::p_within_tbp`].
+    pub fn runs_quantile(&self, q: f64) -> f64 {
+        let groups = self.farmed_groups();
+        if groups.is_empty() {
+            return 0.0;
+        }
+        if q >= 1.0 || self.groups.iter().any(|(_, pieces)| pieces.iter().any(|(_, p)| *p <= 0.0)) {
+            return f64::INFINITY;
+        }
+        if let [pieces] = groups.as_slice() {
+            return self.group_runs_quantile(pieces, q) as f64;
+        }
+        let total = self.total_runs(&groups);
+        // allow for rounding in the cumulative sums
+        let mut cumulative = 0.0;
+        let bin = total.p.iter().position(|p| {
+            cumulative += p;
+            cumulative >= q - 1e-12
+        });
+        bin.map_or(f64::INFINITY, |bin| (total.first + bin * total.width) as f64)
+    }
+
+    pub fn tbp_quantile(&self, q: f64) -> f64 {
+        self.runs_quantile(q) * self.drop_model.run_tbp()
+    }
+
+    pub fn days_quantile(&self, q: f64) -> f64 {
+        self.drop_model.days_for_tbp(self.tbp_quantile(q))
+    }
+
+    /// Probability of every piece dropping when spending `tbp`
+    ///
+    /// Domains that take too many runs to add up run by run are added up in bins of several
+    /// runs instead, counting each bin as done at its last run. That's up to a bin per domain
+    /// late, under 0.3% of the runs a domain is expected to take.
+    pub fn p_within_tbp(&self, tbp: f64) -> f64 {
+        let runs = (tbp / self.drop_model.run_tbp()).floor().max(0.0) as usize;
+        let groups = self.farmed_groups();
+        match groups.as_slice() {
+            [] => 1.0,
+            [pieces] => self.group_cdf(pieces, runs),
+            _ => {
+                let total = self.total_runs(&groups);
+                let bins = match runs.checked_sub(total.first) {
+                    Some(after) => (after / total.width + 1).min(total.p.len()),
+                    None => 0,
+                };
+                total.p[..bins].iter().sum()
+            }
+        }
+    }
+
+    pub fn p_within_days(&self, days: f64) -> f64 {
+        self.p_within_tbp(self.drop_model.tbp_within_days(days))
+    }
+
+    // Pieces of any set drop in every domain of their kind, so they are farmed along with the
+    // first domain of that kind that doesn't have their slot yet, and only farmed on their own
+    // when there is none. Their drops in the other domains are left out.
+    fn farmed_groups(&self) -> Vec<Vec<(RelicSlot, f64)>> {
+        let mut groups = self.groups.iter()
+            .filter(|((domain, _), _)| domain.is_some())
+            .map(|(farmed_in, pieces)| (*farmed_in, pieces.clone()))
+            .collect::<Vec<_>>();
+        for ((_, kind), pieces) in self.groups.iter().filter(|((domain, _), _)| domain.is_none()) {
+            for &(slot, p) in pieces {
+                let domain = groups.iter_mut()
+                    .find(|((_, k), farmed)| k == kind && farmed.iter().all(|(s, _)| *s != slot));
+                match domain {
+                    Some((_, farmed)) => farmed.push((slot, p)),
+                    None => groups.push(((None, *kind), vec![(slot, p)])),
+                }
+            }
+        }
+        groups.into_iter().map(|(_, pieces)| pieces).collect()
+    }
+
+    // probability of a run dropping at least one of `pieces`, for every subset of them
+    fn subsets(&self, pieces: &[(RelicSlot, f64)]) -> Vec<(usize, f64)> {
+        (1..1usize << pieces.len())
+            .map(|mask| {
+                let p = pieces.iter().enumerate().filter(|(i, _)| mask & 1 << i != 0).map(|(_, (_, p))| p).sum::<f64>();
+                (mask.count_ones() as usize, self.drop_model.p_run(p))
+            })
+            .collect()
+    }
+
+    // by inclusion-exclusion over the pieces that haven't dropped yet
+    fn group_cdf(&self, pieces: &[(RelicSlot, f64)], runs: usize) -> f64 {
+        let cdf = 1.0 + self.subsets(pieces).iter()
+            .map(|(n, p_run)| if n % 2 == 1 { -1.0 } else { 1.0 } * (runs as f64 * (-p_run).ln_1p()).exp())
+            .sum::<f64>();
+        cdf.clamp(0.0, 1.0)
+    }
+
+    fn group_expected_runs(&self, pieces: &[(RelicSlot, f64)]) -> f64 {
+        self.subsets(pieces).iter()
+            .map(|(n, p_run)| if n % 2 == 1 { 1.0 } else { -1.0 } / p_run)
+            .sum()
+    }
+
+    fn group_runs_quantile(&self, pieces: &[(RelicSlot, f64)], q: f64) -> usize {
+        let mut high = 1;
+        while self.group_cdf(pieces, high) < q {
+            high *= 2;
+        }
+        let (mut low, mut high) = (0, high);
+        while low + 1 < high {
+            let mid = (low + high) / 2;
+            if self.group_cdf(pieces, mid) < q { low = mid } else { high = mid }
+        }
+        high
+    }
+
+    // Runs of every domain added up, from where each has most likely finished to where it
+    // almost certainly has, in bins small enough that there are at most `MAX_BINS` per domain
+    fn total_runs(&self, groups: &[Vec<(RelicSlot, f64)>]) -> Bins {
+        const MAX_BINS: usize = 8192;
+        let tail = 1e-9 / groups.len() as f64;
+        let ranges = groups.iter()
+            .map(|pieces| {
+                let first = self.group_runs_quantile(pieces, tail).saturating_sub(1);
+                (first, self.group_runs_quantile(pieces, 1.0 - tail))
+            })
+            .collect::<Vec<_>>();
+        let width = ranges.iter().map(|(first, last)| (last - first).div_ceil(MAX_BINS)).max().unwrap_or(1).max(1);
+
+        groups.iter().zip(ranges)
+            .map(|(pieces, (first, last))| {
+                // the first bin also takes the little there is before it
+                let ends = (first..=last).step_by(width).map(|run| run + width - 1);
+                let mut done = 0.0;
+                let p = ends
+                    .map(|end| {
+                        let cdf = self.group_cdf(pieces, end);
+                        let p = cdf - done;
+                        done = cdf;
+                        p
+                    })
+                    .collect();
+                Bins { first: first + width - 1, width, p }
+            })
+            .reduce(|total, group| total.add(&group))
+            .expect("there are several groups")
+    }
+}
+
+// probabilities of `p.len()` runs `width` apart, starting at `first`
+struct Bins {
+    first: usize,
+    width: usize,
+    p: Vec<f64>,
+}
+
+impl Bins {
+    // distribution of the sum of the two, binned the same way
+    fn add(&self, other: &Bins) -> Bins {
+        let mut p = vec![0.0; self.p.len() + other.p.len() - 1];
+        for (i, a) in self.p.iter().enumerate().filter(|(_, a)| **a > 0.0) {
+            for (j, b) in other.p.iter().enumerate() {
+                p[i + j] += a * b;
+            }
+        }
+        Bins { first: self.first + other.first, width: self.width, p }
+    }
+}
+
+#[derive(Debug)]
+pub struct InvalidPieceError(String);
+
+impl fmt::Display for InvalidPieceError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "invalid piece: {}", self.0)
+    }
+}
+
+impl std::error::Error for InvalidPieceError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::assert_float_eq;
+    use crate::RelicSet;
+
+    #[test]
+    fn completion() {
+        let one_per_run = DropModel::new().drops_per_run(vec![(1, 1.0)]);
+        let genius = Some(RelicSet::Genius.domain());
+
+        // a single piece is the geometric case
+        let single = CompletionEstimate::new(one_per_run.clone()).piece(genius, RelicSlot::Head, 0.1).unwrap();
+        assert_float_eq(10.0, single.expected_runs());
+        assert_float_eq(22.0, single.runs_quantile(0.9));
+        assert_float_eq(1.0 - 0.9f64.powi(7), single.p_within_tbp(300.0));
+
+        // coupon collector: two pieces of 0.5 each take 1/0.5 + 1/0.5 - 1/1 runs
+        let pair = CompletionEstimate::new(one_per_run.clone())
+            .piece(genius, RelicSlot::Head, 0.5).unwrap()
+            .piece(genius, RelicSlot::Hands, 0.5).unwrap();
+        assert_float_eq(3.0, pair.expected_runs());
+        // both after two runs only if they were different, 2 * 0.5 * 0.5
+        assert_float_eq(0.5, pair.p_within_tbp(80.0));
+        assert_float_eq(0.0, pair.p_within_tbp(40.0));
+
+        // planar pieces need runs of their own
+        let split = CompletionEstimate::new(one_per_run.clone())
+            .piece(genius, RelicSlot::Head, 0.5).unwrap()
+            .piece(None, RelicSlot::Orb, 0.5).unwrap();
+        assert_float_eq(4.0, split.expected_runs());
+        assert_float_eq(0.25, split.p_within_tbp(80.0));
+        for q in [0.5, 0.9, 0.99] {
+            assert!(split.p_within_tbp(split.tbp_quantile(q)) >= q);
+            assert!(split.p_within_tbp(split.tbp_quantile(q) - 40.0) < q);
+        }
+
+        // more drops per run finish sooner, and a piece that never drops never finishes
+        let doubled = CompletionEstimate::new(one_per_run.clone().drop_multiplier(2))
+            .piece(genius, RelicSlot::Head, 0.5).unwrap()
+            .piece(genius, RelicSlot::Hands, 0.5).unwrap();
+        assert!(doubled.expected_runs() < 3.0);
+        let never = doubled.clone().piece(genius, RelicSlot::Body, 0.0).unwrap();
+        assert_eq!(f64::INFINITY, never.tbp_quantile(0.5));
+
+        // a drop is one piece at most, and a slot is only farmed once per domain
+        assert!(doubled.clone().piece(genius, RelicSlot::Body, 1.5).is_err());
+        assert!(doubled.clone().piece(genius, RelicSlot::Body, -0.1).is_err());
+        assert!(doubled.clone().piece(genius, RelicSlot::Head, 0.5).is_err());
+        assert!(doubled.piece(None, RelicSlot::Head, 0.5).is_ok());
+
+        // a piece of any set drops in the domain farmed for the others too
+        let any_set = CompletionEstimate::new(one_per_run.clone())
+            .piece(None, RelicSlot::Hands, 0.5).unwrap()
+            .piece(genius, RelicSlot::Head, 0.5).unwrap();
+        assert_float_eq(pair.expected_runs(), any_set.expected_runs());
+        assert_float_eq(pair.p_within_tbp(80.0), any_set.p_within_tbp(80.0));
+
+        // rare pieces take no time, whatever the budget
+        let rare = CompletionEstimate::new(one_per_run).piece(genius, RelicSlot::Head, 1e-4).unwrap();
+        assert_float_eq(1.0 - (1.0 - 1e-4f64).powi(10_000), rare.p_within_tbp(400_000.0));
+        assert_float_eq(1.0, rare.p_within_tbp(1e15));
+        let rare = rare.piece(None, RelicSlot::Orb, 2e-4).unwrap();
+        assert_float_eq(15_000.0, rare.expected_runs());
+        // the runs of both add up to at most n with probability sum over r of
+        // P(head at r) * P(orb within n - r)
+        let n = 20_000;
+        let exact = (1..n)
+            .map(|r| 1e-4 * (1.0 - 1e-4f64).powi(r - 1) * (1.0 - (1.0 - 2e-4f64).powi(n - r)))
+            .sum::<f64>();
+        assert!((exact - rare.p_within_tbp(n as f64 * 40.0)).abs() < 1e-3);
+        let median = rare.runs_quantile(0.5);
+        assert!(rare.p_within_tbp(median * 40.0) >= 0.5 && rare.p_within_tbp((median - 1.0) * 40.0) < 0.5);
+    }
+}
@@ -0,0 +1,484 @@
+use std::collections::HashMap;
+use std::hash::{BuildHasherDefault, Hasher};
+
+use serde::Serialize;
+
+use crate::{InvalidRelicError, Relic, RelicScorer, RelicStat, RollTier, SubstatRoll};
+
+// scores are compared on this grid so float noise doesn't split equal scores
+const SCORE_RESOLUTION: f64 = 1e-6;
+
+pub(crate) fn score_key(score: f64) -> i64 {
+    (score / SCORE_RESOLUTION).round() as i64
+}
+
+type Pmf = HashMap<i64, f64, BuildHasherDefault<KeyHasher>>;
+
+// score keys are already well spread integers, hashing them like arbitrary data is most of
+// the time spent on a distribution
+#[derive(Default)]
+struct KeyHasher(u64);
+
+impl Hasher for KeyHasher {
+    fn finish(&self) -> u64 {
+        self.0
+    }
+
+    fn write(&mut self, bytes: &[u8]) {
+        for byte in bytes {
+            self.0 = (self.0.rotate_left(5) ^ *byte as u64).wrapping_mul(0x51_7c_c1_b7_27_22_0a_95);
+        }
+    }
+
+    fn write_i64(&mut self, key: i64) {
+        let hash = (key as u64).wrapping_mul(0x9e37_79b9_7f4a_7c15);
+        // hash tables pick buckets by the low bits, which the multiplication leaves alone
+        self.0 = hash ^ (hash >> 32);
+    }
+}
+
+/// A score that is a sum over the substat rolls of a relic, e.g. weighted roll counts or
+/// weighted stat values. This is what [`Relic::score_distribution`] can compute exactly.
+pub struct LinearScore(Box<dyn Fn(SubstatRoll, usize) -> f64>);
+
+impl LinearScore {
+    /// `roll_score` gets each roll along with the rarity of the relic it is on
+    pub fn new(roll_score: impl Fn(SubstatRoll, usize) -> f64 + 'static) -> Self {
+        Self(Box::new(roll_score))
+    }
+
+    /// Every roll of a stat is worth its weight, regardless of tier
+    pub fn roll_weights(weights: HashMap<RelicStat, f64>) -> Self {
+        Self::new(move |roll, _| weights.get(&roll.stat).copied().unwrap_or(0.0))
+    }
+
+    /// Every point of a stat is worth its weight
+    pub fn value_weights(weights: HashMap<RelicStat, f64>) -> Self {
+        Self::new(move |roll, rarity| weights.get(&roll.stat).copied().unwrap_or(0.0) * roll.value(rarity))
+    }
+
+    pub fn roll_score(&self, roll: SubstatRoll, rarity: usize) -> f64 {
+        (self.0)(roll, rarity)
+    }
+
+    pub fn score(&self, relic: &Relic) -> f64 {
+        relic.subs.iter()
+            .map(|r| self.roll_score(*r, relic.rarity))
+            .sum()
+    }
+}
+
+/// Probability distribution of the score of a relic over all its possible substats
+#[derive(Clone, Debug)]
+pub struct ScoreDistribution {
+    keys: Vec<i64>,
+    points: Vec<(f64, f64)>,
+    // tail[i] = P(score >= points[i].0)
+    tail: Vec<f64>,
+}
+
+impl ScoreDistribution {
+    /// From `(score, probability)` pairs, equal scores are merged
+    pub(crate) fn from_outcomes(outcomes: impl IntoIterator<Item=(f64, f64)>) -> Self {
+        let mut pmf = Pmf::default();
+        for (score, p) in outcomes {
+            *pmf.entry(score_key(score)).or_insert(0.0) += p;
+        }
+        Self::from_pmf(pmf)
+    }
+
+    fn from_pmf(pmf: Pmf) -> Self {
+        let mut pmf = pmf.into_iter()
+            .filter(|(_, p)| *p > 0.0)
+            .collect::<Vec<_>>();
+        pmf.sort_by_key(|(k, _)| *k);
+
+        let keys = pmf.iter().map(|(k, _)| *k).collect::<Vec<_>>();
+        let points = pmf.iter()
+            .map(|(k, p)| (*k as f64 * SCORE_RESOLUTION, *p))
+            .collect::<Vec<_>>();
+
+        let mut tail = points.iter()
+            .rev()
+            .scan(0.0, |acc, (_, p)| {
+                *acc += p;
+                Some(*acc)
+            })
+            .collect::<Vec<_>>();
+        tail.reverse();
+
+        Self { keys, points, tail }
+    }
+
+    fn shifted(self, offset: f64) -> Self {
+        if offset == 0.0 {
+            return self;
+        }
+        Self::from_outcomes(self.points.into_iter().map(|(score, p)| (score + offset, p)))
+    }
+
+    /// Every possible score with its probability, in increasing order of score
+    pub fn points(&self) -> &[(f64, f64)] {
+        &self.points
+    }
+
+    /// P(score <= `score`)
+    pub fn cdf(&self, score: f64) -> f64 {
+        1.0 - self.p_above(score)
+    }
+
+    /// P(score >= `score`)
+    pub fn p_at_least(&self, score: f64) -> f64 {
+        let i = self.keys.partition_point(|k| *k < score_key(score));
+        self.tail.get(i).copied().unwrap_or(0.0)
+    }
+
+    /// P(score > `score`)
+    pub fn p_above(&self, score: f64) -> f64 {
+        let i = self.keys.partition_point(|k| *k <= score_key(score));
+        self.tail.get(i).copied().unwrap_or(0.0)
+    }
+
+    pub fn mean(&self) -> f64 {
+        self.points.iter().map(|(s, p)| s * p).sum()
+    }
+
+    /// Expected amount by which the score exceeds `score`, counting lower scores as 0
+    pub fn expected_gain(&self, score: f64) -> f64 {
+        self.points.iter().map(|(s, p)| (s - score).max(0.0) * p).sum()
+    }
+
+    pub fn variance(&self) -> f64 {
+        let mean = self.mean();
+        self.points.iter().map(|(s, p)| (s - mean).powi(2) * p).sum()
+    }
+
+    pub fn std_dev(&self) -> f64 {
+        self.variance().sqrt()
+    }
+
+    /// Smallest score `s` with P(score <= s) >= `q`
+    pub fn quantile(&self, q: f64) -> f64 {
+        let mut cumulative = 0.0;
+        for (score, p) in &self.points {
+            cumulative += p;
+            // allow for rounding in the cumulative sum
+            if cumulative >= q - 1e-12 {
+                return *score;
+            }
+        }
+        self.points.last().map(|(s, _)| *s).unwrap_or(0.0)
+    }
+
+    pub fn median(&self) -> f64 {
+        self.quantile(0.5)
+    }
+
+    /// Where `score` sits among every possible outcome
+    pub fn rank(&self, score: f64) -> PercentileRank {
+        let above = self.p_above(score);
+        let at_least = self.p_at_least(score);
+        PercentileRank {
+            below: 1.0 - at_least,
+            equal: at_least - above,
+            above,
+        }
+    }
+}
+
+/// Position of a score within a [`ScoreDistribution`], as probabilities of a random relic
+/// scoring below, equal to, or above it
+#[derive(Clone, Copy, Debug, PartialEq, Serialize)]
+pub struct PercentileRank {
+    pub below: f64,
+    pub equal: f64,
+    pub above: f64,
+}
+
+impl PercentileRank {
+    /// Fraction of relics scoring at least as high, e.g. `0.032` for "top 3.2%"
+    pub fn top(&self) -> f64 {
+        self.equal + self.above
+    }
+
+    /// Percentile (0-100) counting ties as half below, half above
+    pub fn percentile(&self) -> f64 {
+        (self.below + self.equal / 2.0) * 100.0
+    }
+}
+
+impl std::fmt::Display for PercentileRank {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let top = self.top() * 100.0;
+        if top >= 10.0 {
+            write!(f, "top {top:.0}%")
+        } else if top >= 0.1 {
+            write!(f, "top {top:.1}%")
+        } else {
+            write!(f, "top {top:.3}%")
+        }
+    }
+}
+
+impl Relic {
+    /// Distribution of `score` over every possible set of substats of this relic's rarity
+    /// and main stat. Equivalent to [`Relic::filtered_p_sub`] with a score threshold, but
+    /// computed in one pass when the score is linear. Fails for a relic that can't drop.
+    pub fn score_distribution(&self, score: &(impl RelicScorer + ?Sized)) -> Result<ScoreDistribution, InvalidRelicError> {
+        match score.as_linear() {
+            Some(linear) => {
+                self.validate_kind()?;
+                let mut leveled = self.clone();
+                leveled.level = self.max_level();
+                Ok(self.linear_score_distribution(linear).shifted(score.fixed_score(&leveled)))
+            }
+            None => self.score_distribution_by(|r| score.score(r)),
+        }
+    }
+
+    fn linear_score_distribution(&self, score: &LinearScore) -> ScoreDistribution {
+        // Substat lines are drawn one at a time (weighted, without replacement) and every
+        // upgrade picks one of the lines uniformly like in `p_sub_u`. Drawing order is
+        // irrelevant to the score, so states are only keyed by the set of lines drawn so far
+        // and the number of upgrades not yet given to one of them.
+        let pool = RelicStat::possible_sub_stats()
+            .filter(|sub| *sub != self.main)
+            .collect::<Vec<_>>();
+        let total_weight = RelicStat::substat_probability_weight_total() as f64
+            - self.main.substat_probability_weight() as f64;
+
+        let max_initial = self.rarity.saturating_sub(1);
+        let min_initial = max_initial.saturating_sub(1);
+        let mut result = Pmf::default();
+        for initial in min_initial..=max_initial {
+            let rolls = initial + self.rarity;
+            let lines = rolls.min(4);
+            let upgrades = rolls - lines;
+
+            let p_line = self.p_initial_lines(initial);
+
+            // score of a single line with n rolls
+            let line_pmfs = pool.iter()
+                .map(|&stat| (1..=upgrades + 1)
+                    .map(|n| self.line_pmf(score, stat, n))
+                    .collect::<Vec<_>>())
+                .collect::<Vec<_>>();
+
+            let mut states = HashMap::new();
+            states.insert((0u16, upgrades), Pmf::from_iter([(0, 1.0)]));
+
+            for drawn in 0..lines {
+                let parts = lines - drawn;
+                let mut next = HashMap::new();
+
+                for ((mask, remaining), pmf) in states {
+                    let used_weight = pool.iter()
+                        .enumerate()
+                        .filter(|(i, _)| mask & (1 << i) != 0)
+                        .map(|(_, s)| s.substat_probability_weight() as f64)
+                        .sum::<f64>();
+
+                    for (i, stat) in pool.iter().enumerate() {
+                        if mask & (1 << i) != 0 {
+                            continue;
+                        }
+                        let p_stat = stat.substat_probability_weight() as f64 / (total_weight - used_weight);
+
+                        // the last line takes all remaining upgrades
+                        let counts = if parts == 1 { remaining..=remaining } else { 0..=remaining };
+                        for count in counts {
+                            let p_count = if parts == 1 {
+                                1.0
+                            } else {
+                                // binomial share of the remaining upgrades for this line
+                                let p_part = 1.0 / parts as f64;
+                                binom(remaining, count)
+                                    * p_part.powi(count as i32)
+                                    * (1.0 - p_part).powi((remaining - count) as i32)
+                            };
+
+                            let entry = next.entry((mask | 1 << i, remaining - count)).or_insert_with(Pmf::default);
+                            convolve_into(entry, &pmf, &line_pmfs[i][count], p_stat * p_count);
+                        }
+                    }
+                }
+                states = next;
+            }
+
+            for pmf in states.into_values() {
+                for (k, p) in pmf {
+                    *result.entry(k).or_insert(0.0) += p * p_line;
+                }
+            }
+        }
+
+        ScoreDistribution::from_pmf(result)
+    }
+
+    /// Where this relic's `score` sits among every possible relic of its rarity and main stat,
+    /// fails if the relic itself isn't valid
+    pub fn percentile_rank(&self, score: &(impl RelicScorer + ?Sized)) -> Result<PercentileRank, InvalidRelicError> {
+        self.validate()?;
+        Ok(self.score_distribution(score)?.rank(score.score(self)))
+    }
+
+    /// Distribution of an arbitrary `score` over every possible set of substats of this
+    /// relic's rarity and main stat. Enumerates every relic, same as
+    /// [`Relic::score_distribution`] does for scores that aren't linear.
+    pub fn score_distribution_by(&self, mut score: impl FnMut(&Relic) -> f64) -> Result<ScoreDistribution, InvalidRelicError> {
+        self.validate_kind()?;
+        Ok(ScoreDistribution::from_outcomes(self.outcomes().map(|(relic, p)| (score(&relic), p))))
+    }
+
+    fn line_pmf(&self, score: &LinearScore, stat: RelicStat, rolls: usize) -> Pmf {
+        let roll = RollTier::all()
+            .map(|tier| (score_key(score.roll_score(SubstatRoll::new(stat, tier), self.rarity)), 1.0 / 3.0))
+            .fold(Pmf::default(), |mut pmf, (k, p)| {
+                *pmf.entry(k).or_insert(0.0) += p;
+                pmf
+            });
+
+        (0..rolls).fold(Pmf::from_iter([(0, 1.0)]), |acc, _| {
+            let mut pmf = Pmf::default();
+            convolve_into(&mut pmf, &acc, &roll, 1.0);
+            pmf
+        })
+    }
+}
+
+fn convolve_into(out: &mut Pmf, a: &Pmf, b: &Pmf, scale: f64) {
+    for (ka, pa) in a {
+        for (kb, pb) in b {
+            *out.entry(ka + kb).or_insert(0.0) += pa * pb * scale;
+        }
+    }
+}
+
+fn binom(n: usize, k: usize) -> f64 {
+    (0..k).fold(1.0, |acc, i| acc * (n - i) as f64 / (i + 1) as f64)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::assert_float_eq;
+    use crate::{RelicSlot, SubstatIterator};
+
+    #[test]
+    fn matches_enumeration() {
+        // tier dependent score on a small relic, so brute force stays cheap
+        let score = LinearScore::new(|roll, _| match roll.stat {
+            RelicStat::CritRate | RelicStat::CritDmg => 2.0 + roll.tier.index() as f64 * 0.25,
+            RelicStat::Spd => 1.0,
+            RelicStat::AtkPercent => 0.5 * roll.tier.index() as f64,
+            _ => 0.0,
+        });
+
+        for main in [RelicStat::HpPercent, RelicStat::CritRate] {
+            let relic = Relic::new(3, RelicSlot::Body, main);
+            let distribution = relic.score_distribution(&score).unwrap();
+
+            assert_float_eq(1.0, distribution.p_at_least(f64::MIN));
+            assert_eq!(
+                distribution.points().len(),
+                relic.score_distribution_by(|r| score.score(r)).unwrap().points().len(),
+            );
+            for threshold in [2.0, 4.5] {
+                assert_float_eq(
+                    relic.filtered_p_sub(|r| score.score(r) >= threshold).unwrap(),
+                    distribution.p_at_least(threshold),
+                );
+                assert_float_eq(
+                    relic.filtered_p_sub(|r| score.score(r) > threshold).unwrap(),
+                    distribution.p_above(threshold),
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn matches_enumeration_with_upgrades() {
+        // every tier of a 4* relic, lines and upgrades only on a 5* one where the tiers of
+        // nine rolls would take too long
+        let by_tier = LinearScore::new(|roll, _| match roll.stat {
+            RelicStat::CritRate | RelicStat::CritDmg => 1.0 + roll.tier.index() as f64 * 0.5,
+            RelicStat::Spd => 1.5,
+            _ => 0.0,
+        });
+        let relic = Relic::new(4, RelicSlot::Feet, RelicStat::Spd);
+        let distribution = relic.score_distribution(&by_tier).unwrap();
+        let enumerated = relic.score_distribution_by(|r| by_tier.score(r)).unwrap();
+        assert_eq!(enumerated.points().len(), distribution.points().len());
+        for (expected, actual) in enumerated.points().iter().zip(distribution.points()) {
+            assert_float_eq(expected.0, actual.0);
+            assert_float_eq(expected.1, actual.1);
+        }
+
+        let by_stat = LinearScore::new(|roll, _| match roll.stat {
+            RelicStat::CritRate | RelicStat::CritDmg => 1.0,
+            RelicStat::Spd => 1.5,
+            RelicStat::AtkPercent => 0.5,
+            _ => 0.0,
+        });
+        for rarity in [4, 5] {
+            let relic = Relic::new(rarity, RelicSlot::Body, RelicStat::HpPercent);
+            let distribution = relic.score_distribution(&by_stat).unwrap();
+            let enumerated = ScoreDistribution::from_outcomes(SubstatIterator::new_from_relic(&relic).map(|subs| {
+                let rolls = relic.copy_with_new_subs(subs.iter().copied());
+                (by_stat.score(&rolls), rolls.p_sub_line() * rolls.p_sub_i() * rolls.p_sub_u())
+            }));
+            assert_eq!(enumerated.points().len(), distribution.points().len());
+            for (expected, actual) in enumerated.points().iter().zip(distribution.points()) {
+                assert_float_eq(expected.0, actual.0);
+                assert_float_eq(expected.1, actual.1);
+            }
+        }
+    }
+
+    #[test]
+    fn summary_statistics() {
+        let distribution = ScoreDistribution::from_pmf(Pmf::from_iter([
+            (score_key(1.0), 0.5),
+            (score_key(2.0), 0.25),
+            (score_key(4.0), 0.25),
+        ]));
+
+        assert_float_eq(2.0, distribution.mean());
+        assert_float_eq(1.5, distribution.variance());
+        assert_float_eq(1.0, distribution.median());
+        assert_float_eq(2.0, distribution.quantile(0.75));
+        assert_float_eq(4.0, distribution.quantile(0.9));
+        assert_float_eq(0.75, distribution.cdf(2.0));
+        assert_float_eq(0.75, distribution.cdf(3.9));
+        assert_float_eq(0.5, distribution.p_at_least(2.0));
+        assert_float_eq(0.25, distribution.p_above(2.0));
+    }
+
+    #[test]
+    fn percentile_rank() {
+        let distribution = ScoreDistribution::from_pmf(Pmf::from_iter([
+            (score_key(1.0), 0.5),
+            (score_key(2.0), 0.25),
+            (score_key(4.0), 0.25),
+        ]));
+
+        let rank = distribution.rank(2.0);
+        assert_float_eq(0.5, rank.below);
+        assert_float_eq(0.25, rank.equal);
+        assert_float_eq(0.25, rank.above);
+        assert_float_eq(0.5, rank.top());
+        assert_float_eq(62.5, rank.percentile());
+        assert_eq!("top 25%", distribution.rank(4.0).to_string());
+        assert_eq!("top 100%", distribution.rank(0.0).to_string());
+
+        // a relic with every possible crit roll is in the top bracket
+        let crit = LinearScore::roll_weights(HashMap::from([(RelicStat::CritRate, 1.0), (RelicStat::CritDmg, 1.0)]));
+        let mut relic = Relic::new(3, RelicSlot::Head, RelicStat::Hp)
+            .copy_with_new_subs([RelicStat::CritRate, RelicStat::CritDmg, RelicStat::Atk, RelicStat::Def, RelicStat::CritDmg]);
+        assert!(relic.percentile_rank(&crit).is_err());
+        relic.level = 9;
+        let rank = relic.percentile_rank(&crit).unwrap();
+        assert_float_eq(0.0, rank.above);
+        assert!(rank.top() < 0.05);
+    }
+}
@@ -0,0 +1,357 @@
+use std::fmt;
+use std::str::FromStr;
+
+const TBP_PER_FUEL: f64 = 60.0;
+
+/// How relics drop from a domain, and how much TBP there is to spend on it
+///
+/// Parses from and displays as `key=value` pairs, e.g.
+/// `tbp=40,drops=2:0.9/3:0.1,mult=2,regen=240,fuel=1,reserve=2400`. Missing keys keep
+/// their default, negative numbers, free runs and days without any TBP don't parse.
+#[derive(Clone, Debug, PartialEq)]
+pub struct DropModel {
+    tbp_per_run: f64,
+    // (number of 5* relics, probability) for a single run
+    drops_per_run: Vec<(u32, f64)>,
+    drop_multiplier: u32,
+    regen_per_day: f64,
+    fuel_per_day: f64,
+    reserve: f64,
+}
+
+impl Default for DropModel {
+    fn default() -> Self {
+        Self {
+            tbp_per_run: 40.0,
+            drops_per_run: vec![(2, 0.9), (3, 0.1)],
+            drop_multiplier: 1,
+            regen_per_day: 240.0,
+            fuel_per_day: 0.0,
+            reserve: 0.0,
+        }
+    }
+}
+
+impl DropModel {
+    pub fn new() -> Self { Self::default() }
+    pub fn tbp_per_run(mut self, tbp: f64) -> Self {
+        self.tbp_per_run = tbp;
+        self
+    }
+    pub fn drops_per_run(mut self, drops: Vec<(u32, f64)>) -> Self {
+        self.drops_per_run = drops;
+        self
+    }
+    /// Drop multiplier of double drop events
+    pub fn drop_multiplier(mut self, multiplier: u32) -> Self {
+        self.drop_multiplier = multiplier;
+        self
+    }
+    pub fn regen_per_day(mut self, tbp: f64) -> Self {
+        self.regen_per_day = tbp;
+        self
+    }
+    /// Fuel used per day, on top of natural regeneration
+    pub fn fuel_per_day(mut self, fuel: f64) -> Self {
+        self.fuel_per_day = fuel;
+        self
+    }
+    /// Reserved TBP available up front
+    pub fn reserve(mut self, tbp: f64) -> Self {
+        self.reserve = tbp;
+        self
+    }
+
+    pub fn tbp_per_relic(&self) -> f64 {
+        self.tbp_per_run / self.relics_per_run()
+    }
+
+    /// Expected 5* relics per run
+    pub fn relics_per_run(&self) -> f64 {
+        self.drops_per_run.iter()
+            .map(|(n, p)| (n * self.drop_multiplier) as f64 * p)
+            .sum()
+    }
+
+    pub fn tbp_per_day(&self) -> f64 {
+        self.regen_per_day + self.fuel_per_day * TBP_PER_FUEL
+    }
+
+    /// Days needed to have `tbp` to spend, reserve first
+    pub fn days_for_tbp(&self, tbp: f64) -> f64 {
+        (tbp - self.reserve).max(0.0) / self.tbp_per_day()
+    }
+
+    /// TBP available to spend within `days`
+    pub fn tbp_within_days(&self, days: f64) -> f64 {
+        self.reserve + days * self.tbp_per_day()
+    }
+
+    /// `--drop-model <spec>` from command line arguments, the default model when it isn't given
+    pub fn from_args(args: impl IntoIterator<Item=String>) -> Result<Self, ParseDropModelError> {
+        let mut args = args.into_iter().skip_while(|arg| arg != "--drop-model");
+        if args.next().is_none() {
+            return Ok(DropModel::default());
+        }
+        match args.next() {
+            Some(spec) if !spec.starts_with("--") => spec.parse(),
+            _ => Err(ParseDropModelError("`--drop-model` needs a value".to_string())),
+        }
+    }
+
+    pub(crate) fn run_tbp(&self) -> f64 {
+        self.tbp_per_run
+    }
+
+    /// Number of 5* relics a single run drops, from a uniform sample `u` in [0, 1)
+    pub(crate) fn sample_drops(&self, u: f64) -> u32 {
+        let mut u = u;
+        for (n, p) in &self.drops_per_run {
+            if u < *p {
+                return n * self.drop_multiplier;
+            }
+            u -= p;
+        }
+        self.drops_per_run.last().map_or(0, |(n, _)| n * self.drop_multiplier)
+    }
+
+    /// Probability of a single run dropping at least one success, when every relic
+    /// independently succeeds with probability `p`
+    pub fn p_run(&self, p: f64) -> f64 {
+        let p_fail = 1.0 - p.clamp(0.0, 1.0);
+        1.0 - self.drops_per_run.iter()
+            .map(|(n, p_n)| p_n * p_fail.powi((n * self.drop_multiplier) as i32))
+            .sum::<f64>()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseDropModelError(String);
+
+impl fmt::Display for ParseDropModelError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "invalid drop model: {}", self.0)
+    }
+}
+
+impl std::error::Error for ParseDropModelError {}
+
+impl FromStr for DropModel {
+    type Err = ParseDropModelError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let number = |key: &str, value: &str| match value.trim().parse::<f64>() {
+            Ok(n) if n.is_finite() && n >= 0.0 => Ok(n),
+            _ => Err(ParseDropModelError(format!("`{key}` is not a non-negative number: `{value}`"))),
+        };
+
+        let mut model = DropModel::default();
+        for pair in s.split(',').map(str::trim).filter(|p| !p.is_empty()) {
+            let (key, value) = pair.split_once('=')
+                .ok_or_else(|| ParseDropModelError(format!("expected key=value, got `{pair}`")))?;
+
+            match key.trim() {
+                "tbp" => model.tbp_per_run = number(key, value)?,
+                "drops" => {
+                    model.drops_per_run = value.split('/')
+                        .map(|drop| {
+                            let (n, p) = drop.split_once(':')
+                                .ok_or_else(|| ParseDropModelError(format!("expected count:probability, got `{drop}`")))?;
+                            let n = n.parse::<u32>()
+                                .map_err(|_| ParseDropModelError(format!("drop count is not an integer: `{n}`")))?;
+                            let p = number(key, p)?;
+                            if p > 1.0 {
+                                return Err(ParseDropModelError(format!("drop probability is more than 1: `{drop}`")));
+                            }
+                            Ok((n, p))
+                        })
+                        .collect::<Result<_, _>>()?;
+
+                    let total = model.drops_per_run.iter().map(|(_, p)| p).sum::<f64>();
+                    if (total - 1.0).abs() > 1e-6 {
+                        return Err(ParseDropModelError(format!("drop probabilities add up to {total}, not 1")));
+                    }
+                }
+                "mult" => model.drop_multiplier = value.parse()
+                    .map_err(|_| ParseDropModelError(format!("`mult` is not an integer: `{value}`")))?,
+                "regen" => model.regen_per_day = number(key, value)?,
+                "fuel" => model.fuel_per_day = number(key, value)?,
+                "reserve" => model.reserve = number(key, value)?,
+                other => return Err(ParseDropModelError(format!("unknown key `{other}`"))),
+            }
+        }
+        // runs have to cost something and there has to be TBP to spend on them
+        if model.tbp_per_run <= 0.0 {
+            return Err(ParseDropModelError(format!("`tbp` has to be more than 0, got {}", model.tbp_per_run)));
+        }
+        if model.tbp_per_day() <= 0.0 {
+            return Err(ParseDropModelError("`regen` and `fuel` add up to no TBP per day".to_string()));
+        }
+        if model.drop_multiplier == 0 {
+            return Err(ParseDropModelError("`mult` has to be at least 1".to_string()));
+        }
+        Ok(model)
+    }
+}
+
+impl fmt::Display for DropModel {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let drops = self.drops_per_run.iter()
+            .map(|(n, p)| format!("{n}:{p}"))
+            .collect::<Vec<_>>()
+            .join("/");
+        write!(
+            f,
+            "tbp={},drops={},mult={},regen={},fuel={},reserve={}",
+            self.tbp_per_run, drops, self.drop_multiplier, self.regen_per_day, self.fuel_per_day, self.reserve
+        )
+    }
+}
+
+/// Distribution of the TBP spent until the first success, when every relic independently
+/// succeeds with probability `p`. The number of runs is geometric.
+#[derive(Clone, Debug)]
+pub struct FarmingEstimate {
+    p: f64,
+    p_run: f64,
+    drop_model: DropModel,
+}
+
+impl FarmingEstimate {
+    pub fn new(p: f64, drop_model: DropModel) -> Self {
+        Self { p, p_run: drop_model.p_run(p), drop_model }
+    }
+
+    /// Probability of a single relic succeeding
+    pub fn p(&self) -> f64 {
+        self.p
+    }
+
+    /// Probability of a single run succeeding
+    pub fn p_run(&self) -> f64 {
+        self.p_run
+    }
+
+    pub fn expected_runs(&self) -> f64 {
+        1.0 / self.p_run
+    }
+
+    pub fn expected_tbp(&self) -> f64 {
+        self.expected_runs() * self.drop_model.tbp_per_run
+    }
+
+    pub fn expected_days(&self) -> f64 {
+        self.drop_model.days_for_tbp(self.expected_tbp())
+    }
+
+    /// Number of runs after which a success has happened with probability `q`
+    pub fn runs_quantile(&self, q: f64) -> f64 {
+        if self.p_run >= 1.0 {
+            return 1.0;
+        }
+        if self.p_run <= 0.0 || q >= 1.0 {
+            return f64::INFINITY;
+        }
+        ((1.0 - q).ln() / (-self.p_run).ln_1p()).ceil().max(1.0)
+    }
+
+    /// TBP after which a success has happened with probability `q`
+    pub fn tbp_quantile(&self, q: f64) -> f64 {
+        self.runs_quantile(q) * self.drop_model.tbp_per_run
+    }
+
+    pub fn days_quantile(&self, q: f64) -> f64 {
+        self.drop_model.days_for_tbp(self.tbp_quantile(q))
+    }
+
+    pub fn median_tbp(&self) -> f64 {
+        self.tbp_quantile(0.5)
+    }
+
+    /// Probability of at least one success when spending `tbp`
+    pub fn p_within_tbp(&self, tbp: f64) -> f64 {
+        let runs = (tbp / self.drop_model.tbp_per_run).floor();
+        1.0 - (1.0 - self.p_run.clamp(0.0, 1.0)).powf(runs)
+    }
+
+    pub fn p_within_days(&self, days: f64) -> f64 {
+        self.p_within_tbp(self.drop_model.tbp_within_days(days))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::assert_float_eq;
+
+    #[test]
+    fn geometric_quantiles() {
+        // one relic per run keeps the numbers simple
+        let drop_model = DropModel::new().drops_per_run(vec![(1, 1.0)]);
+        let estimate = FarmingEstimate::new(0.1, drop_model);
+
+        assert_float_eq(10.0, estimate.expected_runs());
+        assert_float_eq(400.0, estimate.expected_tbp());
+
+        // 1 - 0.9^7 = 0.52
+        assert_float_eq(7.0, estimate.runs_quantile(0.5));
+        assert_float_eq(280.0, estimate.median_tbp());
+        // 1 - 0.9^22 = 0.90
+        assert_float_eq(22.0, estimate.runs_quantile(0.9));
+        assert_float_eq(1.0, estimate.runs_quantile(0.0));
+
+        assert_float_eq(1.0 - 0.9f64.powi(7), estimate.p_within_tbp(300.0));
+        assert_float_eq(0.0, estimate.p_within_tbp(0.0));
+
+        // quantiles and budgets agree
+        for q in [0.5, 0.9, 0.99] {
+            assert!(estimate.p_within_tbp(estimate.tbp_quantile(q)) >= q);
+        }
+
+        assert_eq!(f64::INFINITY, FarmingEstimate::new(0.0, DropModel::default()).tbp_quantile(0.5));
+    }
+
+    #[test]
+    fn drop_model() {
+        let model = DropModel::default();
+        assert_float_eq(2.1, model.relics_per_run());
+        assert_float_eq(40.0 / 2.1, model.tbp_per_relic());
+        assert_float_eq(1.0 - 0.9 * 0.5f64.powi(2) - 0.1 * 0.5f64.powi(3), model.p_run(0.5));
+
+        // double drops, fuel and reserve
+        let model = DropModel::new().drop_multiplier(2).fuel_per_day(1.0).reserve(600.0);
+        assert_float_eq(4.2, model.relics_per_run());
+        assert_float_eq(0.0, model.days_for_tbp(600.0));
+        assert_float_eq(1.0, model.days_for_tbp(900.0));
+        assert_float_eq(900.0, model.tbp_within_days(1.0));
+
+        // string round trip
+        let parsed = "tbp=40, drops=2:0.5/3:0.5, mult=2, fuel=1".parse::<DropModel>().unwrap();
+        assert_eq!(parsed, DropModel::new().drops_per_run(vec![(2, 0.5), (3, 0.5)]).drop_multiplier(2).fuel_per_day(1.0));
+        assert_eq!(parsed, parsed.to_string().parse().unwrap());
+        assert_eq!(DropModel::default(), "".parse().unwrap());
+
+        assert!("drops=2:0.5".parse::<DropModel>().is_err());
+        assert!("tbp=forty".parse::<DropModel>().is_err());
+        assert!("speed=1".parse::<DropModel>().is_err());
+        assert!("tbp=0".parse::<DropModel>().is_err());
+        assert!("tbp=-40".parse::<DropModel>().is_err());
+        assert!("regen=0".parse::<DropModel>().is_err());
+        assert!("regen=0,fuel=0".parse::<DropModel>().is_err());
+        assert!("regen=-240".parse::<DropModel>().is_err());
+        assert!("regen=0,fuel=1".parse::<DropModel>().is_ok());
+        assert!("drops=2:-0.5/3:1.5".parse::<DropModel>().is_err());
+        assert!("drops=2:1.5/3:-0.5".parse::<DropModel>().is_err());
+        assert!("drops=-2:0.5/3:0.5".parse::<DropModel>().is_err());
+        assert!("drops=2.5:0.5/3:0.5".parse::<DropModel>().is_err());
+        assert!("mult=0".parse::<DropModel>().is_err());
+        assert!("reserve=-1".parse::<DropModel>().is_err());
+
+        // from command line arguments, a flag without its value is an error
+        let args = |args: &[&str]| DropModel::from_args(args.iter().map(|arg| arg.to_string()));
+        assert_eq!(DropModel::default(), args(&["cv", "--seed", "1"]).unwrap());
+        assert_eq!(DropModel::new().drop_multiplier(2), args(&["cv", "--drop-model", "mult=2", "--seed", "1"]).unwrap());
+        assert!(args(&["cv", "--drop-model"]).is_err());
+        assert!(args(&["cv", "--drop-model", "--seed", "1"]).is_err());
+    }
+}
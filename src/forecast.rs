@@ -0,0 +1,161 @@
+use itertools::Itertools;
+
+use crate::{canonical_tier, tier_sum_ways, InvalidRelicError, Relic, RelicScorer, RelicStat, ScoreDistribution, SubstatRoll};
+
+impl Relic {
+    /// Upgrades left until the relic is fully leveled
+    pub fn remaining_upgrades(&self) -> usize {
+        self.rarity.saturating_sub(self.level / 3)
+    }
+
+    /// Every way the remaining upgrades of this relic can turn out, with their probability.
+    ///
+    /// Missing lines are drawn like initial substats, then each remaining upgrade picks one
+    /// of the four lines uniformly. Rolls of the same stat are listed once per possible
+    /// total, like [`Relic::filtered_p_sub`] does. Fails if the relic isn't valid.
+    pub fn forecast(&self) -> Result<Vec<(Relic, f64)>, InvalidRelicError> {
+        self.validate()?;
+        let lines = self.subs.iter().map(|r| r.stat).unique().collect::<Vec<_>>();
+        let remaining = self.remaining_upgrades();
+        let new_lines = 4usize.saturating_sub(lines.len()).min(remaining);
+        let upgrades = remaining - new_lines;
+
+        let remaining_weight = RelicStat::substat_probability_weight_total() as f64
+            - self.main.substat_probability_weight() as f64
+            - lines.iter().map(|s| s.substat_probability_weight() as f64).sum::<f64>();
+        let pool = RelicStat::possible_sub_stats()
+            .filter(|s| *s != self.main && !lines.contains(s))
+            .collect::<Vec<_>>();
+
+        let mut outcomes = vec![];
+        for drawn in pool.into_iter().combinations(new_lines) {
+            // same as p_sub_i, summed over every drawing order
+            let p_drawn = drawn.iter()
+                .permutations(new_lines)
+                .map(|perm| perm.iter().fold(
+                    (remaining_weight, 1f64),
+                    |(remaining_weight, product), s| {
+                        let weight = s.substat_probability_weight() as f64;
+                        (remaining_weight - weight, product * weight / remaining_weight)
+                    },
+                ).1)
+                .sum::<f64>();
+
+            let all_lines = lines.iter().chain(&drawn).copied().collect::<Vec<_>>();
+            let line_count = all_lines.len();
+
+            for targets in (0..line_count).combinations_with_replacement(upgrades) {
+                let counts = (0..line_count)
+                    .map(|i| targets.iter().filter(|t| **t == i).count())
+                    .collect::<Vec<_>>();
+                let p_targets = multinomial(&counts) / (line_count as f64).powi(upgrades as i32);
+
+                // new lines get their initial roll on top of any upgrades
+                let new_rolls = counts.iter()
+                    .enumerate()
+                    .map(|(i, n)| n + usize::from(i >= lines.len()))
+                    .collect::<Vec<_>>();
+
+                for tier_sums in new_rolls.iter().map(|&n| 0..=2 * n).multi_cartesian_product() {
+                    let p_tiers = new_rolls.iter()
+                        .zip(&tier_sums)
+                        .map(|(&n, &s)| tier_sum_ways(n, s) as f64 / 3f64.powi(n as i32))
+                        .product::<f64>();
+
+                    // initial rolls of new lines first, so the first four rolls are still the lines
+                    let mut subs = self.subs.clone();
+                    let mut upgrade_rolls = vec![];
+                    for (i, (&n, &s)) in new_rolls.iter().zip(&tier_sums).enumerate() {
+                        let mut rolls = (0..n).map(|j| SubstatRoll::new(all_lines[i], canonical_tier(n, s, j)));
+                        if i >= lines.len() {
+                            subs.extend(rolls.next());
+                        }
+                        upgrade_rolls.extend(rolls);
+                    }
+                    subs.extend(upgrade_rolls);
+
+                    let mut relic = self.copy_with_new_subs(subs);
+                    relic.level = relic.max_level();
+                    outcomes.push((relic, p_drawn * p_targets * p_tiers));
+                }
+            }
+        }
+        Ok(outcomes)
+    }
+
+    /// Distribution of `score` once this relic is fully leveled
+    pub fn forecast_distribution(&self, score: &(impl RelicScorer + ?Sized)) -> Result<ScoreDistribution, InvalidRelicError> {
+        Ok(ScoreDistribution::from_outcomes(self.forecast()?.into_iter().map(|(relic, p)| (score.score(&relic), p))))
+    }
+
+    /// Probability of `filter` passing once this relic is fully leveled
+    pub fn p_forecast(&self, mut filter: impl FnMut(&Relic) -> bool) -> Result<f64, InvalidRelicError> {
+        Ok(self.forecast()?.into_iter()
+            .filter(|(relic, _)| filter(relic))
+            .map(|(_, p)| p)
+            .sum())
+    }
+}
+
+fn multinomial(counts: &[usize]) -> f64 {
+    let factorial = |n: usize| (2..=n).product::<usize>() as f64;
+    factorial(counts.iter().sum()) / counts.iter().map(|&n| factorial(n)).product::<f64>()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::assert_float_eq;
+    use crate::{LinearScore, RelicSlot, RollTier};
+    use RelicStat::*;
+
+    #[test]
+    fn remaining_upgrades() {
+        // fully leveled relics stay as they are
+        {
+            let mut relic = Relic::new(5, RelicSlot::Head, Hp)
+                .copy_with_new_subs([CritRate, CritDmg, Spd, Atk, CritRate, CritRate, CritDmg, Spd, Spd]);
+            relic.level = 15;
+
+            let forecast = relic.forecast().unwrap();
+            assert_eq!(1, forecast.len());
+            assert_eq!(relic.subs, forecast[0].0.subs);
+            assert_float_eq(1.0, forecast[0].1);
+        }
+
+        // one upgrade left: one of four lines, one of three tiers
+        {
+            let mut relic = Relic::new(5, RelicSlot::Head, Hp)
+                .copy_with_new_subs([CritRate, CritDmg, Spd, Atk, CritRate, CritRate, CritDmg, Spd]);
+            relic.level = 12;
+
+            let forecast = relic.forecast().unwrap();
+            assert_eq!(12, forecast.len());
+            assert!(forecast.iter().all(|(r, _)| r.level == 15 && r.subs.len() == 9));
+            assert_float_eq(1.0, forecast.iter().map(|(_, p)| p).sum());
+            assert_float_eq(0.25, relic.p_forecast(|r| r.sub_rolls(CritDmg) == 3).unwrap());
+            assert_float_eq(0.25 / 3.0, relic.p_forecast(|r| {
+                r.subs.last() == Some(&SubstatRoll::new(Spd, RollTier::High))
+            }).unwrap());
+        }
+
+        // a 3 liner gets its fourth line from the pool before upgrading
+        {
+            let relic = Relic::new(5, RelicSlot::Body, CritDmg)
+                .copy_with_new_subs([CritRate, Spd, Atk]);
+
+            let forecast = relic.forecast().unwrap();
+            assert_float_eq(1.0, forecast.iter().map(|(_, p)| p).sum());
+            assert!(forecast.iter().all(|(r, _)| r.subs.len() == 8 && r.subs.iter().map(|s| s.stat).unique().count() == 4));
+            assert_float_eq(0.0, relic.p_forecast(|r| r.sub_rolls(CritDmg) > 0).unwrap());
+            // Hp is 10 out of the 100 - 6 - 6 - 4 - 10 remaining weight
+            assert_float_eq(10.0 / 74.0, relic.p_forecast(|r| r.sub_rolls(Hp) > 0).unwrap());
+
+            let crit = LinearScore::roll_weights([(CritRate, 1.0)].into());
+            let distribution = relic.forecast_distribution(&crit).unwrap();
+            assert_float_eq(1.0, distribution.p_at_least(1.0));
+            // 4 upgrades on 4 lines, none on crit rate
+            assert_float_eq(0.75f64.powi(4), 1.0 - distribution.p_above(1.0));
+        }
+    }
+}
@@ -0,0 +1,226 @@
+use std::collections::HashMap;
+
+use serde::Serialize;
+
+use crate::{LinearScore, Relic, RelicScorer, RelicSlot, RelicStat, RollTier};
+
+// every stat is scored in CRIT DMG points, a max 5* main stat of any stat is worth 64.8
+const MAX_MAIN_CRIT_DMG: f64 = 64.8;
+
+/// Relic score of the fribbels optimizer for a character's stat weights.
+///
+/// Each point of a stat is worth `64.8 / its max 5* main stat value` points of CRIT DMG
+/// times its weight, so a high CRIT DMG roll with weight 1 is worth 6.48. Flat stats count
+/// as their percentage of the character's base stat, and nothing until [`Self::base_stats`]
+/// are given. Body, feet, sphere and rope main stats are scored the same way from their
+/// value at the relic's rarity and level.
+pub struct FribbelsScorer {
+    weights: HashMap<RelicStat, f64>,
+    base_stats: HashMap<RelicStat, f64>,
+    substats: LinearScore,
+}
+
+/// Letter grade of a relic by its share of the best score its slot can have
+#[derive(PartialEq, Eq, PartialOrd, Ord, Copy, Clone, Debug, Serialize)]
+pub enum Grade {
+    F,
+    D,
+    C,
+    B,
+    A,
+    S,
+    SS,
+    SSS,
+}
+
+impl Grade {
+    /// Lowest percentage of the best possible score for each grade
+    const THRESHOLDS: [(Grade, f64); 8] = [
+        (Grade::SSS, 90.0),
+        (Grade::SS, 80.0),
+        (Grade::S, 70.0),
+        (Grade::A, 60.0),
+        (Grade::B, 50.0),
+        (Grade::C, 40.0),
+        (Grade::D, 30.0),
+        (Grade::F, 0.0),
+    ];
+
+    pub fn from_potential(percent: f64) -> Self {
+        Self::THRESHOLDS.iter()
+            .find(|(_, min)| percent >= *min)
+            .map_or(Grade::F, |(grade, _)| *grade)
+    }
+}
+
+impl FribbelsScorer {
+    /// `weights` as set for the character in fribbels, between 0 and 1
+    pub fn new(weights: HashMap<RelicStat, f64>) -> Self {
+        Self::with_base_stats(weights, HashMap::new())
+    }
+
+    /// Base HP, ATK and DEF of the character as in the optimizer's character data, for
+    /// scoring flat stats
+    pub fn base_stats(self, hp: f64, atk: f64, def: f64) -> Self {
+        let base_stats = HashMap::from([(RelicStat::Hp, hp), (RelicStat::Atk, atk), (RelicStat::Def, def)]);
+        Self::with_base_stats(self.weights, base_stats)
+    }
+
+    fn with_base_stats(weights: HashMap<RelicStat, f64>, base_stats: HashMap<RelicStat, f64>) -> Self {
+        let per_point = RelicStat::all()
+            .map(|stat| (stat, point_score(&weights, &base_stats, stat)))
+            .collect::<HashMap<_, _>>();
+        let substats = LinearScore::new(move |roll, rarity| per_point[&roll.stat] * roll.value(rarity));
+        Self { weights, base_stats, substats }
+    }
+
+    pub fn weight(&self, stat: RelicStat) -> f64 {
+        self.weights.get(&stat).copied().unwrap_or(0.0)
+    }
+
+    /// Score of a point of `stat`
+    pub fn point_score(&self, stat: RelicStat) -> f64 {
+        point_score(&self.weights, &self.base_stats, stat)
+    }
+
+    /// Score of the main stat alone
+    pub fn main_score(&self, relic: &Relic) -> f64 {
+        if matches!(relic.slot, RelicSlot::Head | RelicSlot::Hands) {
+            return 0.0;
+        }
+        self.point_score(relic.main) * relic.main_value()
+    }
+
+    /// Best score a fully leveled relic of this rarity and slot could have with these weights
+    pub fn best_score(&self, rarity: usize, slot: RelicSlot) -> f64 {
+        RelicStat::all()
+            .map(|main| Relic::new(rarity, slot, main))
+            .filter(|relic| relic.validate_kind().is_ok())
+            .map(|mut relic| {
+                relic.level = relic.max_level();
+                let mut rolls = RelicStat::possible_sub_stats()
+                    .filter(|stat| *stat != relic.main)
+                    .map(|stat| self.point_score(stat) * stat.sub_roll_value(rarity, RollTier::High))
+                    .collect::<Vec<_>>();
+                rolls.sort_by(|a, b| b.total_cmp(a));
+
+                // every upgrade on the best line
+                let total_rolls = 2 * rarity - 1;
+                let lines = total_rolls.min(4);
+                let subs = rolls.iter().take(lines).sum::<f64>() + rolls[0] * (total_rolls - lines) as f64;
+                self.main_score(&relic) + subs
+            })
+            .fold(0.0, f64::max)
+    }
+
+    /// Score as a percentage of [`Self::best_score`]
+    pub fn potential(&self, relic: &Relic) -> f64 {
+        let best = self.best_score(relic.rarity, relic.slot);
+        if best > 0.0 { 100.0 * self.score(relic) / best } else { 0.0 }
+    }
+
+    pub fn grade(&self, relic: &Relic) -> Grade {
+        Grade::from_potential(self.potential(relic))
+    }
+}
+
+impl RelicScorer for FribbelsScorer {
+    fn score(&self, relic: &Relic) -> f64 {
+        self.main_score(relic) + self.substats.score(relic)
+    }
+
+    fn as_linear(&self) -> Option<&LinearScore> {
+        Some(&self.substats)
+    }
+
+    fn fixed_score(&self, relic: &Relic) -> f64 {
+        self.main_score(relic)
+    }
+}
+
+fn point_score(weights: &HashMap<RelicStat, f64>, base_stats: &HashMap<RelicStat, f64>, stat: RelicStat) -> f64 {
+    let weight = weights.get(&stat).copied().unwrap_or(0.0);
+    match percent_of(stat) {
+        // flat stats count as their percentage of the base stat
+        Some(percent) => base_stats.get(&stat)
+            .map_or(0.0, |base| weight * 100.0 / base * MAX_MAIN_CRIT_DMG / max_main_value(percent)),
+        None => weight * MAX_MAIN_CRIT_DMG / max_main_value(stat),
+    }
+}
+
+fn percent_of(stat: RelicStat) -> Option<RelicStat> {
+    match stat {
+        RelicStat::Hp => Some(RelicStat::HpPercent),
+        RelicStat::Atk => Some(RelicStat::AtkPercent),
+        RelicStat::Def => Some(RelicStat::DefPercent),
+        _ => None,
+    }
+}
+
+// value of a +15 5* main stat, effect res is never a main stat and scores like effect hit rate
+fn max_main_value(stat: RelicStat) -> f64 {
+    let stat = if stat == RelicStat::EffectRes { RelicStat::EffectHitRate } else { stat };
+    stat.main_value(5, 15)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::assert_float_eq;
+    use crate::SubstatRoll;
+    use RelicStat::*;
+
+    #[test]
+    fn fribbels_score() {
+        let scorer = FribbelsScorer::new([(CritRate, 1.0), (CritDmg, 1.0), (Spd, 1.0), (Atk, 0.75), (AtkPercent, 0.75)].into());
+        let high = |stat| SubstatRoll::new(stat, RollTier::High);
+
+        // a high roll of CRIT Rate or CRIT DMG is worth 6.48, SPD a bit more
+        assert_float_eq(6.48, scorer.point_score(CritDmg) * 6.48);
+        assert_float_eq(6.48, scorer.point_score(CritRate) * 3.24);
+        assert_float_eq(2.6 * 64.8 / 25.032, scorer.point_score(Spd) * 2.6);
+        // flat stats are a percentage of the base stat, nothing without one
+        assert_eq!(0.0, scorer.point_score(Atk));
+        let with_base = FribbelsScorer::new([(Atk, 1.0)].into()).base_stats(1000.0, 1000.0, 1000.0);
+        assert_float_eq(1.5 * 0.1, with_base.point_score(Atk));
+
+        // main stat counts on the body at its value for the relic's rarity and level
+        let mut body = Relic::new(5, RelicSlot::Body, CritDmg).copy_with_new_subs([high(CritRate), high(Spd), high(Atk)]);
+        let subs = 6.48 + 2.6 * 64.8 / 25.032;
+        assert_float_eq(10.368 + subs, scorer.score(&body));
+        body.level = 15;
+        assert_float_eq(64.8 + subs, scorer.score(&body));
+        let mut four_star = Relic::new(4, RelicSlot::Body, CritDmg);
+        four_star.level = 12;
+        assert_float_eq(8.2944 + 12.0 * 2.90304, scorer.main_score(&four_star));
+        let feet = Relic::new(5, RelicSlot::Feet, AtkPercent);
+        assert_float_eq(0.75 * 6.912 * 64.8 / 43.2, scorer.main_score(&feet));
+
+        let head = Relic::new(5, RelicSlot::Head, Hp).copy_with_new_subs([high(CritRate)]);
+        assert_float_eq(6.48, scorer.score(&head));
+
+        // best head has 6 SPD rolls, crit and ATK% lines
+        let spd = 2.6 * 64.8 / 25.032;
+        assert_float_eq(6.0 * spd + 6.48 + 6.48 + 0.75 * 6.48, scorer.best_score(5, RelicSlot::Head));
+        assert_eq!(Grade::F, scorer.grade(&head));
+        assert_eq!(Grade::SSS, Grade::from_potential(95.0));
+
+        // flat subs of 4* and 5* relics, against a character with 1000 HP, 500 ATK and 400 DEF
+        let with_base = FribbelsScorer::new([(CritRate, 1.0), (CritDmg, 1.0), (Atk, 0.75), (Hp, 0.5), (Def, 0.25)].into())
+            .base_stats(1000.0, 500.0, 400.0);
+        // +15 5* body: 64.8 main, 3.24 CRIT Rate and 21.168844 ATK, which is 4.2338% of 500 where
+        // 43.2% ATK is worth 64.8
+        let mut five_star = Relic::new(5, RelicSlot::Body, CritDmg).copy_with_new_subs([high(CritRate), high(Atk)]);
+        five_star.level = 15;
+        assert_float_eq(64.8 + 6.48 + 0.75 * 4.2337688 * 1.5, with_base.score(&five_star));
+        // +12 4* body: 43.13088 main, 33.87015 HP (3.387015% of 1000) and 16.935077 DEF (4.2337692%
+        // of 400) where 43.2% HP and 54% DEF are worth 64.8
+        let mut four_star = Relic::new(4, RelicSlot::Body, CritDmg).copy_with_new_subs([high(Hp), high(Def)]);
+        four_star.level = 12;
+        assert_float_eq(43.13088 + 0.5 * 3.387015 * 1.5 + 0.25 * 4.2337692 * 1.2, with_base.score(&four_star));
+
+        // the distribution is shifted by the fully leveled main stat
+        let distribution = Relic::new(4, RelicSlot::Body, CritDmg).score_distribution(&with_base).unwrap();
+        assert_float_eq(CritDmg.main_value(4, 12), distribution.points()[0].0);
+    }
+}
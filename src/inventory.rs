@@ -0,0 +1,409 @@
+use std::collections::{HashMap, HashSet};
+
+use itertools::Itertools;
+use serde::Serialize;
+
+use crate::distribution::score_key;
+use crate::{LinearScore, Relic, RelicScorer, RelicSet, RelicSlot, RelicStat, RollTier, SubstatRoll};
+
+/// A character's scorer and the ids of the relics it wears
+pub struct Build<S> {
+    pub name: String,
+    pub scorer: S,
+    pub equipped: Vec<String>,
+    /// Main stats the character takes by slot, any main stat for slots that aren't listed
+    pub mains: HashMap<RelicSlot, Vec<RelicStat>>,
+    /// Sets the character takes, any set when empty
+    pub sets: Vec<RelicSet>,
+    /// How much the character's score gains count for when planning, 1 by default
+    pub priority: f64,
+}
+
+/// Every relic of an account, looked at for a set of builds
+pub struct Inventory<S> {
+    relics: HashMap<String, Relic>,
+    builds: Vec<Build<S>>,
+    worn: HashSet<String>,
+}
+
+/// How a relic compares to what one build wears in its slot
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct RelicUse {
+    /// Index of the build in [`Inventory::builds`]
+    pub build: usize,
+    pub score: f64,
+    /// Score of the relic the build wears in this slot, 0 for an empty slot
+    pub equipped_score: f64,
+    /// Chance to score higher than the equipped relic once fully leveled
+    pub p_better: f64,
+}
+
+/// Moving a relic from one build to another, which gives back what it wore in that slot
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct Swap {
+    pub relic: String,
+    /// Build that wears the relic now
+    pub from: usize,
+    pub to: usize,
+    /// Relic `to` wears in the slot now, `from` is left without one if there is none
+    pub back: Option<String>,
+    /// Change of each build's score
+    pub from_delta: f64,
+    pub to_delta: f64,
+}
+
+/// What to do with the relics nobody wears
+#[derive(Clone, Debug, Default, PartialEq, Serialize)]
+pub struct InventoryAnalysis {
+    /// Relics that already score higher than what a build wears, one entry per build,
+    /// biggest gain first
+    pub upgrades: Vec<(String, RelicUse)>,
+    /// Relics that aren't upgrades yet but could be once leveled, with the build they are most
+    /// likely to become useful for, most likely first
+    pub prospects: Vec<(String, RelicUse)>,
+    /// Relics that can't beat anything any build wears, even fully leveled
+    pub salvage: Vec<String>,
+}
+
+impl<S> Build<S> {
+    pub fn new(name: impl Into<String>, scorer: S, equipped: impl IntoIterator<Item=String>) -> Self {
+        Self {
+            name: name.into(),
+            scorer,
+            equipped: equipped.into_iter().collect(),
+            mains: HashMap::new(),
+            sets: vec![],
+            priority: 1.0,
+        }
+    }
+
+    /// Only relics with one of `mains` are used in `slot`
+    pub fn accept_mains(mut self, slot: RelicSlot, mains: impl IntoIterator<Item=RelicStat>) -> Self {
+        self.mains.insert(slot, mains.into_iter().collect());
+        self
+    }
+
+    pub fn accept_sets(mut self, sets: impl IntoIterator<Item=RelicSet>) -> Self {
+        self.sets = sets.into_iter().collect();
+        self
+    }
+
+    pub fn priority(mut self, priority: f64) -> Self {
+        self.priority = priority;
+        self
+    }
+
+    /// Main stats the character takes in `slot`
+    pub fn mains(&self, slot: RelicSlot) -> Vec<RelicStat> {
+        match self.mains.get(&slot) {
+            Some(mains) if !mains.is_empty() => mains.iter().copied().filter(|main| slot.mains().any(|m| m == *main)).collect(),
+            _ => slot.mains().collect(),
+        }
+    }
+
+    /// Whether the build takes `relic`'s main stat and set, a relic of an unknown set only goes to
+    /// builds that take any set
+    pub fn accepts(&self, relic: &Relic) -> bool {
+        self.mains.get(&relic.slot).is_none_or(|mains| mains.is_empty() || mains.contains(&relic.main))
+            && (self.sets.is_empty() || relic.set.is_some_and(|set| self.sets.contains(&set)))
+    }
+}
+
+impl Swap {
+    /// Change of the two builds' scores together
+    pub fn gain(&self) -> f64 {
+        self.from_delta + self.to_delta
+    }
+}
+
+impl RelicUse {
+    pub fn gain(&self) -> f64 {
+        self.score - self.equipped_score
+    }
+}
+
+impl<S: RelicScorer> Inventory<S> {
+    pub fn new(relics: HashMap<String, Relic>, builds: Vec<Build<S>>) -> Self {
+        let worn = builds.iter().flat_map(|b| b.equipped.iter().cloned()).collect();
+        Self { relics, builds, worn }
+    }
+
+    /// Relics worn by characters that aren't one of the builds, kept out of the analysis
+    pub fn worn(mut self, ids: impl IntoIterator<Item=String>) -> Self {
+        self.worn.extend(ids);
+        self
+    }
+
+    pub fn relics(&self) -> &HashMap<String, Relic> {
+        &self.relics
+    }
+
+    pub fn builds(&self) -> &[Build<S>] {
+        &self.builds
+    }
+
+    /// Relics nobody wears, by id
+    pub fn unequipped(&self) -> impl Iterator<Item=(&str, &Relic)> {
+        self.relics.iter()
+            .filter(|(id, _)| !self.worn.contains(*id))
+            .map(|(id, relic)| (id.as_str(), relic))
+    }
+
+    /// Id of the relic a build wears in `slot`
+    pub fn equipped(&self, build: usize, slot: RelicSlot) -> Option<&str> {
+        self.builds[build].equipped.iter()
+            .find(|id| self.relics.get(*id).is_some_and(|r| r.slot == slot))
+            .map(String::as_str)
+    }
+
+    /// Score of the relic a build wears in `slot`, 0 when the slot is empty
+    pub fn equipped_score(&self, build: usize, slot: RelicSlot) -> f64 {
+        self.equipped(build, slot)
+            .map_or(0.0, |id| self.builds[build].scorer.score(&self.relics[id]))
+    }
+
+    /// How `relic` compares for every build. Invalid relics can't be forecast and only count
+    /// as they are.
+    pub fn uses(&self, relic: &Relic) -> Vec<RelicUse> {
+        let mut forecast = None;
+        (0..self.builds.len())
+            .map(|build| {
+                let scorer = &self.builds[build].scorer;
+                let score = scorer.score(relic);
+                let equipped_score = self.equipped_score(build, relic.slot);
+
+                let bounds = score_bounds(scorer, relic);
+                let p_better = if !self.builds[build].accepts(relic) {
+                    0.0
+                } else if score_key(score) > score_key(equipped_score)
+                    || bounds.is_some_and(|(min, _)| score_key(min) > score_key(equipped_score)) {
+                    1.0
+                } else if relic.remaining_upgrades() == 0
+                    || bounds.is_some_and(|(_, max)| score_key(max) <= score_key(equipped_score)) {
+                    0.0
+                } else {
+                    // the same for every build, so only done once and only when needed
+                    let outcomes = forecast.get_or_insert_with(|| relic.forecast().unwrap_or_default());
+                    let score = leveled_scorer(scorer, relic);
+                    outcomes.iter()
+                        .filter(|(leveled, _)| score_key(score(leveled)) > score_key(equipped_score))
+                        .map(|(_, p)| p)
+                        .sum()
+                };
+                RelicUse { build, score, equipped_score, p_better }
+            })
+            .collect()
+    }
+
+    /// Sorts every relic nobody wears into upgrades, prospects and salvage
+    pub fn analyze(&self) -> InventoryAnalysis {
+        let mut analysis = InventoryAnalysis::default();
+
+        for (id, relic) in self.unequipped() {
+            let uses = self.uses(relic);
+            let upgrades = uses.iter()
+                .filter(|u| u.p_better == 1.0 && score_key(u.score) > score_key(u.equipped_score))
+                .map(|u| (id.to_string(), u.clone()))
+                .collect::<Vec<_>>();
+
+            if !upgrades.is_empty() {
+                analysis.upgrades.extend(upgrades);
+            } else if let Some(best) = uses.into_iter().filter(|u| u.p_better > 0.0).max_by(|a, b| a.p_better.total_cmp(&b.p_better)) {
+                analysis.prospects.push((id.to_string(), best));
+            } else {
+                analysis.salvage.push(id.to_string());
+            }
+        }
+
+        analysis.upgrades.sort_by(|a, b| b.1.gain().total_cmp(&a.1.gain()).then_with(|| a.0.cmp(&b.0)));
+        analysis.prospects.sort_by(|a, b| b.1.p_better.total_cmp(&a.1.p_better).then_with(|| a.0.cmp(&b.0)));
+        analysis.salvage.sort();
+        analysis
+    }
+
+    /// Every swap of worn relics between two builds that gains more for one than it costs the
+    /// other, biggest gain first. Both builds have to take the main stat they get.
+    pub fn swaps(&self) -> Vec<Swap> {
+        let mut swaps = vec![];
+        for (from, to) in (0..self.builds.len()).cartesian_product(0..self.builds.len()).filter(|(a, b)| a != b) {
+            for id in &self.builds[from].equipped {
+                let Some(relic) = self.relics.get(id) else { continue };
+                let back = self.equipped(to, relic.slot);
+                // pairs of worn relics are looked at from both sides, keep one
+                if back.is_some() && from > to {
+                    continue;
+                }
+                let back_relic = back.map(|id| &self.relics[id]);
+                if !self.builds[to].accepts(relic) || back_relic.is_some_and(|back| !self.builds[from].accepts(back)) {
+                    continue;
+                }
+
+                let (from_scorer, to_scorer) = (&self.builds[from].scorer, &self.builds[to].scorer);
+                let from_delta = back_relic.map_or(0.0, |back| from_scorer.score(back)) - from_scorer.score(relic);
+                let to_delta = to_scorer.score(relic) - back_relic.map_or(0.0, |back| to_scorer.score(back));
+                let swap = Swap { relic: id.clone(), from, to, back: back.map(str::to_string), from_delta, to_delta };
+                if score_key(swap.gain()) > 0 {
+                    swaps.push(swap);
+                }
+            }
+        }
+        swaps.sort_by(|a, b| b.gain().total_cmp(&a.gain()).then_with(|| a.relic.cmp(&b.relic)));
+        swaps
+    }
+
+    /// The best swaps that don't touch the same slot of a build twice, as picked from
+    /// [`Self::swaps`]. Each of them can be done as listed.
+    pub fn recommended_swaps(&self) -> Vec<Swap> {
+        let mut touched = HashSet::new();
+        self.swaps().into_iter()
+            .filter(|swap| {
+                let slot = self.relics[&swap.relic].slot;
+                if touched.contains(&(swap.from, slot)) || touched.contains(&(swap.to, slot)) {
+                    return false;
+                }
+                touched.extend([(swap.from, slot), (swap.to, slot)]);
+                true
+            })
+            .collect()
+    }
+}
+
+// least and most a linear score can reach, if every remaining roll is the worst or best possible
+fn score_bounds(scorer: &(impl RelicScorer + ?Sized), relic: &Relic) -> Option<(f64, f64)> {
+    let linear = scorer.as_linear()?;
+    let rolls = roll_score_range(linear, relic)?;
+
+    let mut leveled = relic.clone();
+    leveled.level = relic.max_level();
+    let score = scorer.fixed_score(&leveled) + linear.score(relic);
+    let upgrades = relic.remaining_upgrades() as f64;
+    let (min, max) = (score + rolls.0.min(0.0) * upgrades, score + rolls.1.max(0.0) * upgrades);
+    // keep float noise from deciding a tie
+    let noise = 1e-9 * max.abs().max(1.0);
+    Some((min - noise, max + noise))
+}
+
+// lowest and highest score a single substat roll of `relic` can have
+pub(crate) fn roll_score_range(linear: &LinearScore, relic: &Relic) -> Option<(f64, f64)> {
+    RelicStat::possible_sub_stats()
+        .filter(|stat| *stat != relic.main)
+        .flat_map(|stat| RollTier::all().map(move |tier| SubstatRoll::new(stat, tier)))
+        .map(|roll| linear.roll_score(roll, relic.rarity))
+        .minmax()
+        .into_option()
+}
+
+// scores fully leveled outcomes of `relic`, linear scores from a table of every roll since
+// scoring each of the many outcomes through the scorer is slow
+fn leveled_scorer<'a>(scorer: &'a (impl RelicScorer + ?Sized), relic: &Relic) -> Box<dyn Fn(&Relic) -> f64 + 'a> {
+    let Some(linear) = scorer.as_linear() else {
+        return Box::new(|leveled| scorer.score(leveled));
+    };
+    let rolls = RelicStat::possible_sub_stats()
+        .flat_map(|stat| RollTier::all().map(move |tier| SubstatRoll::new(stat, tier)))
+        .map(|roll| (roll, linear.roll_score(roll, relic.rarity)))
+        .collect::<HashMap<_, _>>();
+
+    let mut leveled = relic.clone();
+    leveled.level = relic.max_level();
+    let fixed = scorer.fixed_score(&leveled);
+    Box::new(move |leveled| fixed + leveled.subs.iter().map(|roll| rolls[roll]).sum::<f64>())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::FribbelsScorer;
+    use RelicStat::*;
+
+    #[test]
+    fn inventory() {
+        let crit = || FribbelsScorer::new([(CritRate, 1.0), (CritDmg, 1.0)].into());
+        let hp = || FribbelsScorer::new([(HpPercent, 1.0), (Def, 0.5)].into());
+        let head = |level, subs: &[RelicStat]| Relic::try_new(5, RelicSlot::Head, Hp, level, subs.iter().copied()).unwrap();
+
+        let relics = HashMap::from([
+            ("worn crit".to_string(), head(15, &[CritRate, CritDmg, Spd, Atk, CritRate, Spd, Spd, Atk, Atk])),
+            ("worn hp".to_string(), head(15, &[HpPercent, Def, Spd, Atk, HpPercent, HpPercent, Spd, Spd, Atk])),
+            ("better crit".to_string(), head(15, &[CritRate, CritDmg, Spd, Atk, CritRate, CritDmg, CritDmg, Atk, Atk])),
+            ("fresh".to_string(), head(0, &[CritRate, CritDmg, Spd, Atk])),
+            ("junk".to_string(), head(15, &[Spd, Atk, AtkPercent, BreakEffect, Spd, Atk, Atk, AtkPercent, BreakEffect])),
+            ("fresh junk".to_string(), head(0, &[Spd, Atk, AtkPercent, BreakEffect])),
+        ]);
+        let inventory = Inventory::new(relics, vec![
+            Build::new("crit", crit(), ["worn crit".to_string()]),
+            Build::new("hp", hp(), ["worn hp".to_string()]),
+        ]);
+        assert_eq!(4, inventory.unequipped().count());
+        assert_eq!(Some("worn hp"), inventory.equipped(1, RelicSlot::Head));
+        assert_eq!(None, inventory.equipped(1, RelicSlot::Hands));
+
+        let analysis = inventory.analyze();
+        assert_eq!(1, analysis.upgrades.len());
+        let (id, upgrade) = &analysis.upgrades[0];
+        assert_eq!(("better crit", 0), (id.as_str(), upgrade.build));
+        // two more crit dmg rolls instead of crit rate and spd
+        assert!(upgrade.gain() > 0.0);
+
+        // a +0 with both crit lines can still beat three crit rolls, one without can't beat anything
+        assert_eq!(1, analysis.prospects.len());
+        let (id, prospect) = &analysis.prospects[0];
+        assert_eq!(("fresh", 0), (id.as_str(), prospect.build));
+        assert!(0.0 < prospect.p_better && prospect.p_better < 1.0, "{prospect:?}");
+        assert_eq!(vec!["fresh junk", "junk"], analysis.salvage);
+
+        // the pruned result is the same as the full forecast
+        let fresh = &inventory.relics()["fresh"];
+        let exact = fresh.p_forecast(|r| score_key(inventory.builds()[0].scorer.score(r)) > score_key(upgrade.equipped_score)).unwrap();
+        assert!((exact - prospect.p_better).abs() < 0.00001);
+    }
+
+    #[test]
+    fn swaps() {
+        let crit = || FribbelsScorer::new([(CritRate, 1.0), (CritDmg, 1.0)].into());
+        let hp = || FribbelsScorer::new([(HpPercent, 1.0)].into());
+        let relics = HashMap::from([
+            ("hp head".to_string(), Relic::try_new(5, RelicSlot::Head, Hp, 15, [HpPercent, Def, Spd, Atk, HpPercent, HpPercent, Spd, Spd, Atk]).unwrap()),
+            ("crit head".to_string(), Relic::try_new(5, RelicSlot::Head, Hp, 15, [CritRate, CritDmg, Spd, Atk, CritRate, CritDmg, Spd, Spd, Atk]).unwrap()),
+            ("hp body".to_string(), Relic::try_new(5, RelicSlot::Body, HpPercent, 15, [Def, Spd, Atk, CritRate, Def, Def, Spd, Spd, Atk]).unwrap()),
+        ]);
+        let builds = |hp_bodies: &[RelicStat]| vec![
+            Build::new("crit", crit(), ["hp head".to_string(), "hp body".to_string()]),
+            Build::new("hp", hp(), ["crit head".to_string()]).accept_mains(RelicSlot::Body, hp_bodies.iter().copied()),
+            Build::new("crit without relics", crit(), []),
+        ];
+
+        // trading heads helps both, the body costs one crit roll for a whole main stat
+        let inventory = Inventory::new(relics.clone(), builds(&[]));
+        let swaps = inventory.swaps();
+        assert_eq!(3, swaps.len(), "{swaps:?}");
+        let heads = swaps.iter().find(|s| s.relic == "hp head").unwrap();
+        assert_eq!(("hp head", 0, 1, Some("crit head")), (heads.relic.as_str(), heads.from, heads.to, heads.back.as_deref()));
+        assert!(heads.from_delta > 0.0 && heads.to_delta > 0.0);
+        let body = swaps.iter().find(|s| s.relic == "hp body").unwrap();
+        assert_eq!((0, 1, None), (body.from, body.to, body.back.as_deref()));
+        assert!(body.from_delta < 0.0 && body.gain() > heads.gain());
+
+        // the crit head can't go to both
+        let recommended = inventory.recommended_swaps();
+        assert_eq!(2, recommended.len());
+        assert!(!recommended.iter().any(|s| s.relic == "crit head"));
+
+        // the body is off the table when its main stat isn't wanted
+        let inventory = Inventory::new(relics.clone(), builds(&[DefPercent]));
+        assert!(!inventory.swaps().iter().any(|s| s.relic == "hp body"));
+
+        // or when its set isn't, which also goes for a relic of an unknown set
+        let genius_only = |relics: HashMap<String, Relic>| {
+            let mut builds = builds(&[]);
+            builds[1].sets = vec![RelicSet::Genius];
+            Inventory::new(relics, builds)
+        };
+        assert!(!genius_only(relics.clone()).swaps().iter().any(|s| s.to == 1));
+        let mut genius = relics;
+        for relic in genius.values_mut() {
+            *relic = relic.clone().set(RelicSet::Genius);
+        }
+        assert_eq!(swaps, genius_only(genius).swaps());
+    }
+}
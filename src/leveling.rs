@@ -0,0 +1,487 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::str::FromStr;
+
+use crate::distribution::score_key;
+use crate::{DropModel, FarmingEstimate, InvalidRelicError, LinearScore, Relic, RelicStat, RollTier, SubstatRoll};
+
+/// What leveling a relic costs, converted to TBP
+///
+/// There are no built-in numbers: [`LevelingCosts::free`] costs and gives back nothing until
+/// the EXP per upgrade, fodder EXP, refund and exchange rates are set with the builder
+/// methods, or they are all parsed from a spec like `exp5=4000/9000,fodder5=1500,refund=0.8,exp_tbp=0.0025`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct LevelingCosts {
+    // EXP needed for each upgrade (+0 -> +3, +3 -> +6, ...), by rarity
+    exp_per_upgrade: HashMap<usize, Vec<f64>>,
+    // EXP a +0 relic is worth as fodder, by rarity
+    fodder_exp: HashMap<usize, f64>,
+    exp_refund: f64,
+    credits_per_exp: f64,
+    tbp_per_exp: f64,
+    tbp_per_credit: f64,
+}
+
+impl LevelingCosts {
+    /// Leveling that costs nothing
+    pub fn free() -> Self {
+        Self {
+            exp_per_upgrade: HashMap::new(),
+            fodder_exp: HashMap::new(),
+            exp_refund: 0.0,
+            credits_per_exp: 0.0,
+            tbp_per_exp: 0.0,
+            tbp_per_credit: 0.0,
+        }
+    }
+    pub fn exp_per_upgrade(mut self, rarity: usize, exp: Vec<f64>) -> Self {
+        self.exp_per_upgrade.insert(rarity, exp);
+        self
+    }
+    pub fn fodder_exp(mut self, rarity: usize, exp: f64) -> Self {
+        self.fodder_exp.insert(rarity, exp);
+        self
+    }
+    /// Share of the EXP spent on a relic that comes back when feeding it
+    pub fn exp_refund(mut self, refund: f64) -> Self {
+        self.exp_refund = refund;
+        self
+    }
+    pub fn credits_per_exp(mut self, credits: f64) -> Self {
+        self.credits_per_exp = credits;
+        self
+    }
+    pub fn tbp_per_exp(mut self, tbp: f64) -> Self {
+        self.tbp_per_exp = tbp;
+        self
+    }
+    pub fn tbp_per_credit(mut self, tbp: f64) -> Self {
+        self.tbp_per_credit = tbp;
+        self
+    }
+
+    /// EXP to take a relic from +0 through its first `upgrades` upgrades
+    pub fn exp_to_upgrade(&self, rarity: usize, upgrades: usize) -> f64 {
+        self.exp_per_upgrade.get(&rarity)
+            .map(|exp| exp.iter().take(upgrades).sum())
+            .unwrap_or(0.0)
+    }
+
+    /// EXP and credits spent on the first `upgrades` upgrades, in TBP
+    pub fn leveling_tbp(&self, rarity: usize, upgrades: usize) -> f64 {
+        self.exp_to_upgrade(rarity, upgrades) * (self.tbp_per_exp + self.credits_per_exp * self.tbp_per_credit)
+    }
+
+    /// EXP given back by feeding a relic after `upgrades` upgrades, in TBP
+    pub fn feeding_tbp(&self, rarity: usize, upgrades: usize) -> f64 {
+        let fodder = self.fodder_exp.get(&rarity).copied().unwrap_or(0.0);
+        (fodder + self.exp_to_upgrade(rarity, upgrades) * self.exp_refund) * self.tbp_per_exp
+    }
+
+    /// Net cost of leveling a relic through `upgrades` upgrades, then feeding it
+    fn stop_tbp(&self, rarity: usize, upgrades: usize) -> f64 {
+        self.leveling_tbp(rarity, upgrades) - self.feeding_tbp(rarity, upgrades)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseLevelingCostsError(String);
+
+impl fmt::Display for ParseLevelingCostsError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "invalid leveling costs: {}", self.0)
+    }
+}
+
+impl std::error::Error for ParseLevelingCostsError {}
+
+impl FromStr for LevelingCosts {
+    type Err = ParseLevelingCostsError;
+
+    /// `key=value` pairs: `exp<rarity>` (EXP per upgrade, `/` separated), `fodder<rarity>`,
+    /// `refund`, `credits` (per EXP), `exp_tbp` and `credit_tbp`
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let number = |key: &str, value: &str| match value.trim().parse::<f64>() {
+            Ok(n) if n.is_finite() && n >= 0.0 => Ok(n),
+            _ => Err(ParseLevelingCostsError(format!("`{key}` is not a non-negative number: `{value}`"))),
+        };
+        let rarity = |key: &str, prefix: &str| key.strip_prefix(prefix)
+            .and_then(|r| r.parse::<usize>().ok())
+            .filter(|r| (2..=5).contains(r));
+
+        let mut costs = LevelingCosts::free();
+        for pair in s.split(',').map(str::trim).filter(|p| !p.is_empty()) {
+            let (key, value) = pair.split_once('=')
+                .ok_or_else(|| ParseLevelingCostsError(format!("expected key=value, got `{pair}`")))?;
+            let key = key.trim();
+
+            match key {
+                "refund" => costs.exp_refund = number(key, value)?,
+                "credits" => costs.credits_per_exp = number(key, value)?,
+                "exp_tbp" => costs.tbp_per_exp = number(key, value)?,
+                "credit_tbp" => costs.tbp_per_credit = number(key, value)?,
+                _ => if let Some(r) = rarity(key, "exp") {
+                    let exp = value.split('/').map(|exp| number(key, exp)).collect::<Result<_, _>>()?;
+                    costs.exp_per_upgrade.insert(r, exp);
+                } else if let Some(r) = rarity(key, "fodder") {
+                    costs.fodder_exp.insert(r, number(key, value)?);
+                } else {
+                    return Err(ParseLevelingCostsError(format!("unknown key `{key}`")));
+                },
+            }
+        }
+        if costs.exp_refund > 1.0 {
+            return Err(ParseLevelingCostsError(format!("`refund` is more than 1: {}", costs.exp_refund)));
+        }
+        Ok(costs)
+    }
+}
+
+/// One checkpoint of a [`LevelingPolicy`], as a share of the relics with the right main stat
+#[derive(Clone, Debug, PartialEq)]
+pub struct LevelingStep {
+    pub level: usize,
+    /// Relics that get to this level
+    pub p_reached: f64,
+    /// Relics that are fed at this level instead of being upgraded further
+    pub p_stopped: f64,
+    /// Lowest chance of still reaching the target among relics that keep being upgraded
+    pub min_p_continue: f64,
+}
+
+// lines drawn so far and score so far
+type State = (u16, i64);
+
+/// When to stop leveling a relic so the TBP spent per success, farming and leveling
+/// included, is as low as possible
+///
+/// Relics are looked at every upgrade: one that is unlikely to reach `min_score` is fed
+/// right away rather than leveled to the end. Upgrades follow [`Relic::forecast`].
+#[derive(Clone, Debug)]
+pub struct LevelingPolicy {
+    steps: Vec<LevelingStep>,
+    p_success: f64,
+    leveling_tbp: f64,
+    tbp_per_success: f64,
+    tbp_per_success_leveling_all: f64,
+    pool: Vec<RelicStat>,
+    roll_keys: HashMap<SubstatRoll, i64>,
+    // keep leveling, by checkpoint
+    decisions: Vec<HashMap<State, bool>>,
+}
+
+impl LevelingPolicy {
+    /// Best policy for relics of the rarity and main stat of `relic`, each one costing its
+    /// share of the farming TBP of `drop_model`. Fails for a relic that can't drop.
+    pub fn optimize(
+        relic: &Relic,
+        score: &LinearScore,
+        min_score: f64,
+        drop_model: &DropModel,
+        costs: &LevelingCosts,
+    ) -> Result<Self, InvalidRelicError> {
+        let p_main = relic.p_main()?;
+        let farming_tbp = drop_model.tbp_per_relic() / p_main;
+        let upgrades = Upgrades::new(relic, score, min_score, costs);
+        // farming is paid by the run, so the drops after a success in the same run are wasted
+        let tbp_per_success = |evaluation: &Evaluation| {
+            FarmingEstimate::new(p_main * evaluation.p_success, drop_model.clone()).expected_tbp()
+                + evaluation.cost / evaluation.p_success
+        };
+
+        let leveling_all = upgrades.evaluate(None);
+
+        // Dinkelbach: value a success at the current TBP per relic success until it settles
+        let mut worth = (farming_tbp + leveling_all.cost) / leveling_all.p_success;
+        let mut best = leveling_all.clone();
+        for _ in 0..100 {
+            if !worth.is_finite() {
+                break;
+            }
+            let evaluation = upgrades.evaluate(Some(worth));
+            let next = (farming_tbp + evaluation.cost) / evaluation.p_success;
+            best = evaluation;
+            if (worth - next).abs() <= 1e-9 * worth {
+                break;
+            }
+            worth = next;
+        }
+
+        let steps = upgrades.steps(&best, &leveling_all);
+        Ok(Self {
+            steps,
+            p_success: best.p_success,
+            leveling_tbp: best.cost,
+            tbp_per_success: tbp_per_success(&best),
+            tbp_per_success_leveling_all: tbp_per_success(&leveling_all),
+            pool: upgrades.pool,
+            roll_keys: upgrades.roll_keys,
+            decisions: best.decisions,
+        })
+    }
+
+    /// Checkpoints from +0 to the last upgrade
+    pub fn steps(&self) -> &[LevelingStep] {
+        &self.steps
+    }
+
+    /// Probability of a relic with the right main stat ending up as a success
+    pub fn p_success(&self) -> f64 {
+        self.p_success
+    }
+
+    /// Expected net leveling cost of a relic with the right main stat, fed EXP given back
+    pub fn leveling_tbp(&self) -> f64 {
+        self.leveling_tbp
+    }
+
+    /// Expected TBP per success following this policy, farming and leveling included
+    pub fn tbp_per_success(&self) -> f64 {
+        self.tbp_per_success
+    }
+
+    /// Expected TBP per success when every relic is leveled to the end
+    pub fn tbp_per_success_leveling_all(&self) -> f64 {
+        self.tbp_per_success_leveling_all
+    }
+
+    /// Whether `relic` should get its next upgrade, `None` if it is fully leveled or not
+    /// one of the relics this policy is for
+    pub fn keep_leveling(&self, relic: &Relic) -> Option<bool> {
+        let mut mask = 0u16;
+        let mut key = 0;
+        for roll in &relic.subs {
+            mask |= 1 << self.pool.iter().position(|s| *s == roll.stat)?;
+            key += self.roll_keys.get(roll)?;
+        }
+        self.decisions.get(relic.level / 3)?.get(&(mask, key)).copied()
+    }
+}
+
+#[derive(Clone)]
+struct Evaluation {
+    decisions: Vec<HashMap<State, bool>>,
+    // (expected cost, probability of success) per state, by checkpoint
+    values: Vec<HashMap<State, (f64, f64)>>,
+    cost: f64,
+    p_success: f64,
+}
+
+struct Upgrades<'a> {
+    relic: &'a Relic,
+    costs: &'a LevelingCosts,
+    pool: Vec<RelicStat>,
+    total_weight: f64,
+    roll_keys: HashMap<SubstatRoll, i64>,
+    min_key: i64,
+    initial: HashMap<State, f64>,
+    // every reachable state, by checkpoint
+    layers: Vec<Vec<State>>,
+}
+
+impl<'a> Upgrades<'a> {
+    fn new(relic: &'a Relic, score: &LinearScore, min_score: f64, costs: &'a LevelingCosts) -> Self {
+        let pool = RelicStat::possible_sub_stats()
+            .filter(|sub| *sub != relic.main)
+            .collect::<Vec<_>>();
+        let roll_keys = pool.iter()
+            .flat_map(|&stat| RollTier::all().map(move |tier| SubstatRoll::new(stat, tier)))
+            .map(|roll| (roll, score_key(score.roll_score(roll, relic.rarity))))
+            .collect();
+
+        let mut upgrades = Self {
+            relic,
+            costs,
+            pool,
+            total_weight: RelicStat::substat_probability_weight_total() as f64
+                - relic.main.substat_probability_weight() as f64,
+            roll_keys,
+            min_key: score_key(min_score),
+            initial: HashMap::new(),
+            layers: vec![],
+        };
+
+        // initial lines are drawn like new lines
+        let max_initial = relic.rarity.saturating_sub(1);
+        let min_initial = max_initial.saturating_sub(1);
+        for initial in min_initial..=max_initial {
+            let mut states = HashMap::from([((0u16, 0i64), relic.p_initial_lines(initial))]);
+            for _ in 0..initial {
+                states = upgrades.step(&states);
+            }
+            for (state, p) in states {
+                *upgrades.initial.entry(state).or_insert(0.0) += p;
+            }
+        }
+
+        let mut layer = upgrades.initial.clone();
+        upgrades.layers.push(layer.keys().copied().collect());
+        for _ in 0..relic.rarity {
+            layer = upgrades.step(&layer);
+            upgrades.layers.push(layer.keys().copied().collect());
+        }
+        upgrades
+    }
+
+    /// States after one more roll: a new line while there are less than four, otherwise
+    /// one of the four lines uniformly
+    fn next(&self, (mask, key): State) -> Vec<(State, f64)> {
+        let mut next = vec![];
+        if mask.count_ones() < 4 {
+            let used_weight = self.pool.iter()
+                .enumerate()
+                .filter(|(i, _)| mask & (1 << i) != 0)
+                .map(|(_, s)| s.substat_probability_weight() as f64)
+                .sum::<f64>();
+            for (i, stat) in self.pool.iter().enumerate().filter(|(i, _)| mask & (1 << i) == 0) {
+                let p_stat = stat.substat_probability_weight() as f64 / (self.total_weight - used_weight);
+                for tier in RollTier::all() {
+                    let roll_key = self.roll_keys[&SubstatRoll::new(*stat, tier)];
+                    next.push(((mask | 1 << i, key + roll_key), p_stat / 3.0));
+                }
+            }
+        } else {
+            for (_, stat) in self.pool.iter().enumerate().filter(|(i, _)| mask & (1 << i) != 0) {
+                for tier in RollTier::all() {
+                    let roll_key = self.roll_keys[&SubstatRoll::new(*stat, tier)];
+                    next.push(((mask, key + roll_key), 1.0 / 12.0));
+                }
+            }
+        }
+        next
+    }
+
+    fn step(&self, states: &HashMap<State, f64>) -> HashMap<State, f64> {
+        let mut next = HashMap::new();
+        for (&state, p) in states {
+            for (next_state, p_next) in self.next(state) {
+                *next.entry(next_state).or_insert(0.0) += p * p_next;
+            }
+        }
+        next
+    }
+
+    /// Expected cost and success of the best policy when a success is worth
+    /// `tbp_per_success`, or of leveling everything when `None`
+    fn evaluate(&self, tbp_per_success: Option<f64>) -> Evaluation {
+        let rarity = self.relic.rarity;
+        let upgrades = self.layers.len() - 1;
+
+        let mut values = vec![HashMap::new(); upgrades + 1];
+        let mut decisions = vec![HashMap::new(); upgrades];
+        values[upgrades] = self.layers[upgrades].iter()
+            .map(|&(mask, key)| {
+                let value = if key >= self.min_key {
+                    (self.costs.leveling_tbp(rarity, upgrades), 1.0)
+                } else {
+                    (self.costs.stop_tbp(rarity, upgrades), 0.0)
+                };
+                ((mask, key), value)
+            })
+            .collect();
+
+        for k in (0..upgrades).rev() {
+            for &state in &self.layers[k] {
+                let (cost, p_success) = self.next(state).into_iter()
+                    .map(|(next, p)| {
+                        let (cost, p_success) = values[k + 1][&next];
+                        (cost * p, p_success * p)
+                    })
+                    .fold((0.0, 0.0), |(a, b), (c, d)| (a + c, b + d));
+                let stop = self.costs.stop_tbp(rarity, k);
+
+                let keep_leveling = match tbp_per_success {
+                    Some(worth) => cost - worth * p_success < stop,
+                    None => true,
+                };
+                decisions[k].insert(state, keep_leveling);
+                values[k].insert(state, if keep_leveling { (cost, p_success) } else { (stop, 0.0) });
+            }
+        }
+
+        let (cost, p_success) = self.initial.iter()
+            .map(|(state, p)| {
+                let (cost, p_success) = values[0][state];
+                (cost * p, p_success * p)
+            })
+            .fold((0.0, 0.0), |(a, b), (c, d)| (a + c, b + d));
+        Evaluation { decisions, values, cost, p_success }
+    }
+
+    fn steps(&self, policy: &Evaluation, leveling_all: &Evaluation) -> Vec<LevelingStep> {
+        let mut steps = vec![];
+        let mut reached = self.initial.clone();
+        for (k, decisions) in policy.decisions.iter().enumerate() {
+            let mut step = LevelingStep {
+                level: k * 3,
+                p_reached: reached.values().sum(),
+                p_stopped: 0.0,
+                min_p_continue: 1.0,
+            };
+            let mut continued = HashMap::new();
+            for (state, p) in reached {
+                if decisions[&state] {
+                    step.min_p_continue = step.min_p_continue.min(leveling_all.values[k][&state].1);
+                    continued.insert(state, p);
+                } else {
+                    step.p_stopped += p;
+                }
+            }
+            steps.push(step);
+            reached = self.step(&continued);
+        }
+        steps
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::assert_float_eq;
+    use crate::RelicSlot;
+    use RelicStat::*;
+
+    #[test]
+    fn leveling_policy() {
+        let relic = Relic::new(3, RelicSlot::Body, CritRate);
+        let score = LinearScore::roll_weights([(CritDmg, 1.0), (Spd, 1.0)].into());
+        let drop_model = DropModel::default();
+
+        // leveling for free, every relic goes to the end
+        let free = LevelingCosts::free();
+        let policy = LevelingPolicy::optimize(&relic, &score, 2.0, &drop_model, &free).unwrap();
+        let p = relic.score_distribution(&score).unwrap().p_at_least(2.0);
+        assert_float_eq(p, policy.p_success());
+        let p_main = relic.p_main().unwrap();
+        assert_float_eq(FarmingEstimate::new(p_main * p, drop_model.clone()).expected_tbp(), policy.tbp_per_success());
+        assert_float_eq(policy.tbp_per_success(), policy.tbp_per_success_leveling_all());
+
+        // a 5* relic has several upgrades left, so the upgrade probabilities matter too
+        let five = Relic::new(5, RelicSlot::Body, CritRate);
+        let policy = LevelingPolicy::optimize(&five, &score, 4.0, &drop_model, &free).unwrap();
+        assert_float_eq(five.score_distribution(&score).unwrap().p_at_least(4.0), policy.p_success());
+
+        // with costs, hopeless relics are fed early
+        let costs: LevelingCosts = "exp3=1500/3500/6000,fodder3=500,refund=0.8,exp_tbp=0.1".parse().unwrap();
+        assert_eq!(
+            LevelingCosts::free().exp_per_upgrade(3, vec![1500.0, 3500.0, 6000.0]).fodder_exp(3, 500.0).exp_refund(0.8).tbp_per_exp(0.1),
+            costs
+        );
+        for bad in ["exp1=100", "fodder5=-1", "refund=2", "exp_tbp=x", "exp5", "speed=1"] {
+            assert!(bad.parse::<LevelingCosts>().is_err(), "{bad}");
+        }
+        let policy = LevelingPolicy::optimize(&relic, &score, 2.0, &drop_model, &costs).unwrap();
+        assert!(policy.tbp_per_success() < policy.tbp_per_success_leveling_all());
+        assert!(policy.steps().iter().any(|s| s.p_stopped > 0.0));
+        assert_float_eq(1.0, policy.steps()[0].p_reached);
+        assert!(policy.p_success() <= p);
+
+        // four lines of nothing and a single upgrade left: can't reach 2 anymore
+        let mut hopeless = relic.copy_with_new_subs([Hp, Def, DefPercent, Atk]);
+        hopeless.level = 6;
+        assert_eq!(Some(false), policy.keep_leveling(&hopeless));
+        let mut promising = relic.copy_with_new_subs([CritDmg, Spd, Hp]);
+        promising.level = 3;
+        assert_eq!(Some(true), policy.keep_leveling(&promising));
+    }
+}
@@ -1,90 +1,136 @@
 use std::array::IntoIter;
 
 use itertools::Itertools;
-
+use serde::{Deserialize, Serialize};
+
+pub use completion::{CompletionEstimate, InvalidPieceError};
+pub use distribution::{LinearScore, PercentileRank, ScoreDistribution};
+pub use farming::{DropModel, FarmingEstimate, ParseDropModelError};
+pub use fribbels::{FribbelsScorer, Grade};
+pub use inventory::{Build, Inventory, InventoryAnalysis, RelicUse, Swap};
+pub use leveling::{LevelingCosts, LevelingPolicy, LevelingStep, ParseLevelingCostsError};
+pub use names::{ParseRelicSetError, ParseRelicSlotError, ParseRelicStatError};
+pub use planning::FarmingTarget;
 pub use probability::ConditionalRelicProbabilityCalculator;
-
+pub use save::{parse_base_stats, parse_weights, FribbelsSave, Imported, ParseBaseStatsError, ParseFribbelsSaveError, SaveCharacter, SaveForm, SaveMainStat, SaveRelic, SaveSubstat, ScoringOverride};
+pub use scoring::RelicScorer;
+pub use sets::{Domain, RelicSet, SetInfo, SetKind};
+pub use simulation::{CrossCheck, Estimate, FarmingRun, FarmingSimulation, Simulator};
+pub use tables::{GameTables, ParseGameTablesError};
+pub use targets::StatTargets;
+pub use validation::InvalidRelicError;
+
+mod completion;
+mod distribution;
+mod farming;
+mod forecast;
+mod fribbels;
+mod inventory;
+mod leveling;
+mod names;
+mod planning;
 mod probability;
-
-#[derive(Clone, Debug)]
+mod save;
+mod scoring;
+mod sets;
+mod simulation;
+mod tables;
+mod targets;
+mod validation;
+
+/// Serializes with fribbels stat and slot names, e.g.
+/// `{"rarity": 5, "slot": "Head", "main": "HP", "level": 15, "subs": ["SPD", {"stat": "CRIT DMG", "tier": "High"}]}`.
+/// `set`, `level` and `subs` can be left out, and rolls given by stat alone are mid rolls.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
 pub struct Relic {
     pub rarity: usize,
     pub slot: RelicSlot,
     pub main: RelicStat,
-    pub subs: Vec<RelicStat>,
+    /// Unknown or left open when `None`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub set: Option<RelicSet>,
+    /// Enhancement level, +3 per upgrade
+    #[serde(default)]
+    pub level: usize,
+    #[serde(default)]
+    pub subs: Vec<SubstatRoll>,
 }
 
 impl Relic {
     pub fn new(rarity: usize, slot: RelicSlot, main: RelicStat) -> Self {
-        Self { rarity, slot, main, subs: vec![] }
+        Self { rarity, slot, main, set: None, level: 0, subs: vec![] }
+    }
+
+    pub fn set(mut self, set: RelicSet) -> Self {
+        self.set = Some(set);
+        self
     }
 
-    pub fn p_main(&self) -> f64 {
-        self.p_main_set() * self.p_main_slot() * self.p_main_stat()
+    pub fn max_level(&self) -> usize {
+        self.rarity * 3
+    }
+
+    /// Probability of a drop being this set, slot and main stat, fails for a main stat or set
+    /// that can't drop in the slot
+    pub fn p_main(&self) -> Result<f64, InvalidRelicError> {
+        Ok(self.p_main_set() * self.p_main_slot() * self.p_main_stat()?)
     }
 
     pub fn p_main_set(&self) -> f64 {
-        1.0 / 2.0
+        GameTables::with(|tables| tables.p_set())
     }
 
     pub fn p_main_slot(&self) -> f64 {
-        use RelicSlot::*;
-        match self.slot {
-            Head | Hands | Body | Feet => 0.25,
-            Orb | Rope => 0.5
-        }
+        GameTables::with(|tables| tables.p_slot(self.slot))
     }
 
-    pub fn p_main_stat(&self) -> f64 {
-        use RelicSlot::*;
-        use RelicStat::*;
-        match self.slot {
-            Head | Hands => 1.0,
-            Body => match self.main {
-                HpPercent | AtkPercent | DefPercent => 0.2,
-                CritRate | CritDmg | HealingBoost | EffectHitRate => 0.1,
-                _ => 0.0,
-            }
-            Feet => match self.main {
-                HpPercent | AtkPercent | DefPercent => 0.3,
-                Spd => 0.1,
-                _ => 0.0
-            }
-            Orb => match self.main {
-                HpPercent | AtkPercent | DefPercent => 0.12,
-
-                PhysDmgBoost | FireDmgBoost | IceDmgBoost |
-                WindDmgBoost | LightningDmgBoost | QuantumDmgBoost |
-                ImaginaryDmgBoost => 0.65 / 7.0,
+    pub fn p_main_stat(&self) -> Result<f64, InvalidRelicError> {
+        self.validate_kind()?;
+        Ok(self.p_main_stat_unchecked())
+    }
 
-                _ => 0.0
-            }
-            Rope => match self.main {
-                HpPercent | AtkPercent | DefPercent => 0.8 / 3.0,
-                BreakEffect => 0.15,
-                EnergyRegenRate => 0.05,
-                _ => 0.0
-            }
-        }
+    // for relics already checked with `validate_kind`
+    pub(crate) fn p_main_stat_unchecked(&self) -> f64 {
+        GameTables::with(|tables| tables.p_main_stat(self.slot, self.main))
     }
 
-    pub fn p_sub(&self) -> f64 {
-        self.p_sub_line() * self.p_sub_i() * self.p_sub_u()
+    /// Probability of exactly these substats once fully leveled, fails unless `subs` has
+    /// every roll of a fully leveled relic
+    pub fn p_sub(&self) -> Result<f64, InvalidRelicError> {
+        let mut leveled = self.clone();
+        leveled.level = self.max_level();
+        leveled.validate()?;
+        Ok(self.p_sub_line() * self.p_sub_i() * self.p_sub_u())
     }
 
     pub fn p_sub_line(&self) -> f64 {
         let max = self.rarity.saturating_sub(1) + self.rarity;
         if self.subs.len() == max {
-            0.20
+            self.p_initial_lines(self.rarity.saturating_sub(1))
         } else {
-            0.80
+            self.p_initial_lines(self.rarity.saturating_sub(2))
+        }
+    }
+
+    /// Probability of this relic's rarity dropping with `lines` initial lines
+    pub fn p_initial_lines(&self, lines: usize) -> f64 {
+        let p_max = GameTables::with(|tables| tables.p_max_initial_lines());
+        let max = self.rarity.saturating_sub(1);
+        if lines == max {
+            p_max
+        } else if lines + 1 == max {
+            1.0 - p_max
+        } else {
+            0.0
         }
     }
 
     pub fn p_sub_i(&self) -> f64 {
-        let remaining_weight = 100.0 - self.main.substat_probability_weight() as f64;
+        let remaining_weight = RelicStat::substat_probability_weight_total() as f64
+            - self.main.substat_probability_weight() as f64;
 
         self.subs.iter()
+            .map(|r| r.stat)
             .take(4)
             .permutations(self.subs.len().min(4))
             .map(|perm| {
@@ -100,58 +146,276 @@ impl Relic {
     }
 
     pub fn p_sub_u(&self) -> f64 {
-        // Assumes the upgrade probability is uniform
+        // Every upgrade picks one of the four lines uniformly. The upgrades are listed as a
+        // multiset, so this counts every order they could have happened in.
         let factorial = |n| (2..=n).product::<usize>() as f64;
-        let binom = |n, k| factorial(n) / (factorial(k) * factorial(n - k));
 
-        let k = self.subs.len().saturating_sub(4); // number of upgrades
-        let n = 4;
+        let lines = self.subs.len().min(4);
+        let upgrades = &self.subs[lines..];
+        let orders = factorial(upgrades.len()) / self.subs.iter()
+            .take(lines)
+            .map(|line| factorial(upgrades.iter().filter(|r| r.stat == line.stat).count()))
+            .product::<f64>();
+
+        orders / 4f64.powi(upgrades.len() as i32)
+    }
+
+    pub fn p_sub_tier(&self) -> f64 {
+        // Only the total value of a stat is observable, so this is the probability of
+        // the rolled tiers of each stat adding up to what they do, not of their order
+        self.subs.iter()
+            .map(|r| r.stat)
+            .unique()
+            .map(|stat| {
+                let rolls = self.sub_rolls(stat);
+                let tier_sum = self.subs.iter()
+                    .filter(|r| r.stat == stat)
+                    .map(|r| r.tier.index())
+                    .sum::<usize>();
+                tier_sum_ways(rolls, tier_sum) as f64 / 3f64.powi(rolls as i32)
+            })
+            .product()
+    }
 
-        1.0 / binom(n + k - 1, k)
+    /// Number of rolls (initial + upgrades) that went into `stat`
+    pub fn sub_rolls(&self, stat: RelicStat) -> usize {
+        self.subs.iter().filter(|r| r.stat == stat).count()
     }
 
-    pub fn copy_with_new_subs(&self, subs: Vec<RelicStat>) -> Self {
+    /// Total substat value of `stat`, percentages are in percent (e.g. `6.48` Crit DMG)
+    pub fn sub_value(&self, stat: RelicStat) -> f64 {
+        self.subs.iter()
+            .filter(|r| r.stat == stat)
+            .map(|r| r.value(self.rarity))
+            .sum()
+    }
+
+    /// Value of the main stat at the relic's level
+    pub fn main_value(&self) -> f64 {
+        self.main.main_value(self.rarity, self.level.min(self.max_level()))
+    }
+
+    /// Total of `stat` on the relic, main stat and substats
+    pub fn stat_value(&self, stat: RelicStat) -> f64 {
+        let main = if self.main == stat { self.main_value() } else { 0.0 };
+        main + self.sub_value(stat)
+    }
+
+    pub fn copy_with_new_subs(&self, subs: impl IntoIterator<Item=impl Into<SubstatRoll>>) -> Self {
         Self {
             rarity: self.rarity,
             slot: self.slot,
             main: self.main,
-            subs,
+            set: self.set,
+            level: self.level,
+            subs: subs.into_iter().map(Into::into).collect(),
         }
     }
 
-    pub fn filtered_p_sub(&self, filter: impl FnMut(&Relic) -> bool) -> f64 {
+    /// Probability of the fully leveled relic passing `filter`, fails for a relic that can't drop
+    pub fn filtered_p_sub(&self, mut filter: impl FnMut(&Relic) -> bool) -> Result<f64, InvalidRelicError> {
+        self.validate_kind()?;
+        Ok(self.outcomes()
+            .filter(|(r, _)| filter(r))
+            .map(|(_, p)| p)
+            .sum())
+    }
+
+    /// Probability of the fully leveled substats adding up to at least the value of each of
+    /// `mins`. Given how often each stat rolls its values are independent, so the tiers don't
+    /// need to be enumerated like for [`Self::filtered_p_sub`].
+    pub fn p_sub_at_least(&self, mins: &[(RelicStat, f64)]) -> Result<f64, InvalidRelicError> {
+        self.validate_kind()?;
+        Ok(SubstatIterator::new_from_relic(self)
+            .map(|subs| {
+                let rolls = self.copy_with_new_subs(subs.iter().copied());
+                let p_rolls = rolls.p_sub_line() * rolls.p_sub_i() * rolls.p_sub_u();
+                p_rolls * mins.iter()
+                    .map(|(stat, min)| {
+                        let n = subs.iter().filter(|s| *s == stat).count();
+                        let (low, step) = stat.sub_roll_low_and_step(self.rarity);
+                        (0..=2 * n)
+                            // allow for rounding in the stat values
+                            .filter(|tier_sum| low * n as f64 + step * *tier_sum as f64 >= min - 1e-9)
+                            .map(|tier_sum| tier_sum_ways(n, tier_sum) as f64 / 3f64.powi(n as i32))
+                            .sum::<f64>()
+                    })
+                    .product::<f64>()
+            })
+            .sum())
+    }
+
+    // every possible fully leveled set of substats for this relic's rarity and main, with its p_sub
+    fn outcomes(&self) -> impl Iterator<Item=(Relic, f64)> + '_ {
+        let mut leveled = self.clone();
+        leveled.level = self.max_level();
         SubstatIterator::new_from_relic(self)
-            .map(|subs| self.copy_with_new_subs(subs))
-            .filter(filter)
-            .map(|r| r.p_sub())
-            .sum()
+            .flat_map(move |subs| {
+                // line, initial and upgrade probabilities don't depend on tiers
+                let rolls = self.copy_with_new_subs(subs.iter().copied());
+                let p = rolls.p_sub_line() * rolls.p_sub_i() * rolls.p_sub_u();
+
+                let leveled = leveled.clone();
+                RollTierIterator::new(subs).map(move |(subs, p_tier)| (leveled.copy_with_new_subs(subs), p * p_tier))
+            })
+    }
+}
+
+/// A single substat roll. The first roll of a stat is its initial line, every further
+/// roll of the same stat is an upgrade.
+#[derive(PartialEq, Eq, Hash, Ord, PartialOrd, Copy, Clone, Debug, Serialize, Deserialize)]
+#[serde(from = "SubstatRollRepr")]
+pub struct SubstatRoll {
+    pub stat: RelicStat,
+    pub tier: RollTier,
+}
+
+// a roll is either `{"stat": ..., "tier": ...}` or just its stat
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum SubstatRollRepr {
+    Roll { stat: RelicStat, tier: RollTier },
+    Stat(RelicStat),
+}
+
+impl From<SubstatRollRepr> for SubstatRoll {
+    fn from(repr: SubstatRollRepr) -> Self {
+        match repr {
+            SubstatRollRepr::Roll { stat, tier } => Self { stat, tier },
+            SubstatRollRepr::Stat(stat) => stat.into(),
+        }
+    }
+}
+
+impl SubstatRoll {
+    pub fn new(stat: RelicStat, tier: RollTier) -> Self {
+        Self { stat, tier }
+    }
+
+    pub fn value(&self, rarity: usize) -> f64 {
+        self.stat.sub_roll_value(rarity, self.tier)
+    }
+
+    /// Recovers the rolls of a stat from its displayed total value, e.g. from a save file.
+    ///
+    /// Only the sum of the tiers can be recovered, so the tiers are assigned canonically
+    /// (highs first, then at most one mid, then lows), same as [`Relic::filtered_p_sub`]
+    /// enumerates them.
+    pub fn from_value(stat: RelicStat, rarity: usize, rolls: usize, value: f64) -> Vec<SubstatRoll> {
+        let (low, step) = stat.sub_roll_low_and_step(rarity);
+        let tier_sum = if step > 0.0 {
+            ((value - low * rolls as f64) / step).round().clamp(0.0, 2.0 * rolls as f64) as usize
+        } else {
+            0
+        };
+
+        (0..rolls)
+            .map(|i| SubstatRoll::new(stat, canonical_tier(rolls, tier_sum, i)))
+            .collect()
+    }
+}
+
+impl From<RelicStat> for SubstatRoll {
+    /// Rolls of unknown tier are taken as mid rolls, which is also the average roll value
+    fn from(stat: RelicStat) -> Self {
+        Self { stat, tier: RollTier::Mid }
+    }
+}
+
+/// The three equally likely values a substat roll can take
+#[derive(PartialEq, Eq, Hash, Ord, PartialOrd, Copy, Clone, Debug, Serialize, Deserialize)]
+pub enum RollTier {
+    Low,
+    Mid,
+    High,
+}
+
+impl RollTier {
+    pub fn all() -> IntoIter<RollTier, 3> {
+        [RollTier::Low, RollTier::Mid, RollTier::High].into_iter()
+    }
+
+    pub fn index(&self) -> usize {
+        match self {
+            RollTier::Low => 0,
+            RollTier::Mid => 1,
+            RollTier::High => 2,
+        }
+    }
+}
+
+// number of ways `rolls` tiers (0, 1 or 2 each) can add up to `tier_sum`
+fn tier_sum_ways(rolls: usize, tier_sum: usize) -> usize {
+    if tier_sum > 2 * rolls {
+        return 0;
+    }
+    if rolls == 0 {
+        return 1;
+    }
+    (tier_sum.saturating_sub(2)..=tier_sum)
+        .map(|s| tier_sum_ways(rolls - 1, s))
+        .sum()
+}
+
+// tier of the `i`th roll in the canonical assignment of `tier_sum` over `rolls` rolls
+fn canonical_tier(rolls: usize, tier_sum: usize, i: usize) -> RollTier {
+    debug_assert!(tier_sum <= 2 * rolls);
+    let highs = tier_sum / 2;
+    let mids = tier_sum % 2;
+    if i < highs {
+        RollTier::High
+    } else if i < highs + mids {
+        RollTier::Mid
+    } else {
+        RollTier::Low
     }
 }
 
 
-#[derive(PartialEq, Eq, Hash, Ord, PartialOrd, Copy, Clone, Debug)]
+#[derive(PartialEq, Eq, Hash, Ord, PartialOrd, Copy, Clone, Debug, Serialize, Deserialize)]
 pub enum RelicStat {
+    #[serde(rename = "HP")]
     Hp,
+    #[serde(rename = "ATK")]
     Atk,
+    #[serde(rename = "DEF")]
     Def,
+    #[serde(rename = "HP%")]
     HpPercent,
+    #[serde(rename = "ATK%")]
     AtkPercent,
+    #[serde(rename = "DEF%")]
     DefPercent,
+    #[serde(rename = "SPD")]
     Spd,
+    #[serde(rename = "CRIT Rate")]
     CritRate,
+    #[serde(rename = "CRIT DMG")]
     CritDmg,
+    #[serde(rename = "Effect Hit Rate")]
     EffectHitRate,
+    #[serde(rename = "Effect RES")]
     EffectRes,
+    #[serde(rename = "Break Effect")]
     BreakEffect,
     // Main stat only
+    #[serde(rename = "Energy Regeneration Rate")]
     EnergyRegenRate,
+    #[serde(rename = "Outgoing Healing Boost")]
     HealingBoost,
+    #[serde(rename = "Physical DMG Boost")]
     PhysDmgBoost,
+    #[serde(rename = "Fire DMG Boost")]
     FireDmgBoost,
+    #[serde(rename = "Ice DMG Boost")]
     IceDmgBoost,
+    #[serde(rename = "Wind DMG Boost")]
     WindDmgBoost,
+    #[serde(rename = "Lightning DMG Boost")]
     LightningDmgBoost,
+    #[serde(rename = "Quantum DMG Boost")]
     QuantumDmgBoost,
+    #[serde(rename = "Imaginary DMG Boost")]
     ImaginaryDmgBoost,
 }
 
@@ -174,37 +438,83 @@ impl RelicStat {
         ].into_iter()
     }
 
-    // source: https://docs.qq.com/sheet/DYkFxSVFNSGp5YlVv?tab=metuhj
-    pub fn substat_probability_weight(&self) -> u8 {
+    pub fn all() -> IntoIter<RelicStat, 21> {
         use RelicStat::*;
-        match self {
-            Hp => 10,
-            Atk => 10,
-            Def => 10,
-            HpPercent => 10,
-            AtkPercent => 10,
-            DefPercent => 10,
-            Spd => 4,
-            CritRate => 6,
-            CritDmg => 6,
-            EffectHitRate => 8,
-            EffectRes => 8,
-            BreakEffect => 8,
-            _ => 0
-        }
+        [
+            Hp,
+            Atk,
+            Def,
+            HpPercent,
+            AtkPercent,
+            DefPercent,
+            Spd,
+            CritRate,
+            CritDmg,
+            EffectHitRate,
+            EffectRes,
+            BreakEffect,
+            EnergyRegenRate,
+            HealingBoost,
+            PhysDmgBoost,
+            FireDmgBoost,
+            IceDmgBoost,
+            WindDmgBoost,
+            LightningDmgBoost,
+            QuantumDmgBoost,
+            ImaginaryDmgBoost,
+        ].into_iter()
+    }
+
+    pub fn substat_probability_weight(&self) -> u8 {
+        GameTables::with(|tables| tables.substat_weight(*self))
+    }
+
+    /// Sum of the weights of every possible substat
+    pub fn substat_probability_weight_total() -> u32 {
+        GameTables::with(|tables| tables.substat_weight_total())
+    }
+
+    /// Value of a single substat roll, percentages are in percent (e.g. `6.48` Crit DMG)
+    pub fn sub_roll_value(&self, rarity: usize, tier: RollTier) -> f64 {
+        let (low, step) = self.sub_roll_low_and_step(rarity);
+        low + step * tier.index() as f64
+    }
+
+    /// Value of this stat as a main stat at `level`, percentages are in percent
+    pub fn main_value(&self, rarity: usize, level: usize) -> f64 {
+        let (base, step) = GameTables::with(|tables| tables.main_base_and_step(*self, rarity));
+        base + step * level as f64
+    }
+
+    fn sub_roll_low_and_step(&self, rarity: usize) -> (f64, f64) {
+        GameTables::with(|tables| tables.sub_roll_low_and_step(*self, rarity))
     }
 }
 
-#[derive(Debug, Clone, Copy)]
+#[derive(PartialEq, Eq, Hash, Debug, Clone, Copy, Serialize, Deserialize)]
 pub enum RelicSlot {
     Head,
     Hands,
     Body,
     Feet,
+    #[serde(rename = "PlanarSphere")]
     Orb,
+    #[serde(rename = "LinkRope")]
     Rope,
 }
 
+impl RelicSlot {
+    pub fn all() -> IntoIter<RelicSlot, 6> {
+        use RelicSlot::*;
+        [Head, Hands, Body, Feet, Orb, Rope].into_iter()
+    }
+
+    /// Main stats that can drop in this slot
+    pub fn mains(&self) -> impl Iterator<Item=RelicStat> + '_ {
+        RelicStat::all().filter(|main| GameTables::with(|tables| tables.p_main_stat(*self, *main)) > 0.0)
+    }
+}
+
 struct SubstatIterator(Box<dyn Iterator<Item=Vec<RelicStat>>>);
 
 impl SubstatIterator {
@@ -253,6 +563,64 @@ impl Iterator for SubstatIterator {
     }
 }
 
+/// Expands the rolls of a [`SubstatIterator`] item into every distinguishable set of tiers,
+/// along with the probability of those tiers ([`Relic::p_sub_tier`]).
+///
+/// Rolls of the same stat only differ by their total, so one canonical tier assignment is
+/// generated per possible total.
+struct RollTierIterator(Box<dyn Iterator<Item=(Vec<SubstatRoll>, f64)>>);
+
+impl RollTierIterator {
+    pub fn new(subs: Vec<RelicStat>) -> RollTierIterator {
+        let stats = subs.iter().copied().unique().collect::<Vec<_>>();
+        let rolls = stats.iter()
+            .map(|stat| subs.iter().filter(|s| *s == stat).count())
+            .collect::<Vec<_>>();
+
+        if stats.is_empty() {
+            return RollTierIterator(Box::new(std::iter::once((vec![], 1.0))));
+        }
+
+        // probability of each tier total for each stat
+        let p_tier_sums = rolls.iter()
+            .map(|&n| (0..=2 * n).map(|s| tier_sum_ways(n, s) as f64 / 3f64.powi(n as i32)).collect::<Vec<_>>())
+            .collect::<Vec<_>>();
+
+        RollTierIterator(Box::new(
+            rolls.iter()
+                .map(|&n| 0..=2 * n)
+                .multi_cartesian_product()
+                .map(move |tier_sums| {
+                    let mut seen = vec![0; stats.len()];
+                    let subs = subs.iter()
+                        .map(|&stat| {
+                            let i = stats.iter().position(|s| *s == stat).unwrap();
+                            let tier = canonical_tier(rolls[i], tier_sums[i], seen[i]);
+                            seen[i] += 1;
+                            SubstatRoll::new(stat, tier)
+                        })
+                        .collect();
+                    let p = tier_sums.iter().enumerate().map(|(i, &s)| p_tier_sums[i][s]).product();
+                    (subs, p)
+                })
+        ))
+    }
+}
+
+impl Iterator for RollTierIterator {
+    type Item = (Vec<SubstatRoll>, f64);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.0.next()
+    }
+}
+
+#[cfg(test)]
+pub(crate) fn assert_float_eq(a: f64, b: f64) {
+    let epsilon = 0.00001;
+    assert!(epsilon > (a - b).abs(), "{a} != {b}")
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -277,6 +645,8 @@ mod tests {
                 rarity: 5,
                 slot: RelicSlot::Head,
                 main: RelicStat::Hp,
+                set: None,
+                level: 0,
                 subs: Vec::new(),
             };
 
@@ -294,6 +664,8 @@ mod tests {
                 rarity: 5,
                 slot: RelicSlot::Head,
                 main: RelicStat::Hp,
+                set: None,
+                level: 0,
                 subs: Vec::new(),
             };
 
@@ -312,6 +684,8 @@ mod tests {
                 rarity: 5,
                 slot: RelicSlot::Head,
                 main: RelicStat::Hp,
+                set: None,
+                level: 0,
                 subs: Vec::new(),
             };
 
@@ -329,6 +703,8 @@ mod tests {
                 rarity: 5,
                 slot: RelicSlot::Head,
                 main: RelicStat::Hp,
+                set: None,
+                level: 0,
                 subs: Vec::new(),
             };
 
@@ -349,8 +725,110 @@ mod tests {
         }
     }
 
-    fn assert_float_eq(a: f64, b: f64) {
-        let epsilon = 0.00001;
-        assert!(epsilon > (a - b).abs())
+    #[test]
+    fn roll_tiers() {
+        // Tier totals follow the (1 + x + x^2)^n coefficients
+        {
+            assert_eq!(tier_sum_ways(2, 0), 1);
+            assert_eq!(tier_sum_ways(2, 2), 3);
+            assert_eq!(tier_sum_ways(3, 3), 7);
+            assert_eq!((0..=12).map(|s| tier_sum_ways(6, s)).sum::<usize>(), 729);
+        }
+
+        // Values from a save are split back into rolls
+        {
+            let rolls = SubstatRoll::from_value(RelicStat::CritDmg, 5, 2, 12.312);
+            assert_eq!(rolls, vec![
+                SubstatRoll::new(RelicStat::CritDmg, RollTier::High),
+                SubstatRoll::new(RelicStat::CritDmg, RollTier::Mid),
+            ]);
+
+            let relic = Relic::new(5, RelicSlot::Head, RelicStat::Hp).copy_with_new_subs(rolls);
+            assert_float_eq(12.312, relic.sub_value(RelicStat::CritDmg));
+            assert_float_eq(2.0 / 9.0, relic.p_sub_tier());
+        }
+
+        // Tier expansion of a single roll outcome adds up to 1, and agrees with p_sub_tier
+        {
+            use RelicStat::*;
+            let relic = Relic::new(5, RelicSlot::Head, Hp);
+            let rolls = vec![Spd, CritRate, CritDmg, Atk, Spd, Spd, CritDmg, Spd, Spd];
+
+            assert_eq!(RollTierIterator::new(rolls.clone()).count(), 11 * 5 * 3 * 3);
+            assert_float_eq(1.0, RollTierIterator::new(rolls.clone()).map(|(_, p)| p).sum());
+            for (subs, p) in RollTierIterator::new(rolls) {
+                assert_float_eq(p, relic.copy_with_new_subs(subs).p_sub_tier());
+            }
+        }
+
+        // Same roll counts, different values
+        {
+            let relic = Relic::new(5, RelicSlot::Head, RelicStat::Hp);
+            let rolls = vec![RelicStat::Spd, RelicStat::Atk, RelicStat::Def, RelicStat::DefPercent, RelicStat::Spd];
+            let p = RollTierIterator::new(rolls)
+                .map(|(subs, p)| (relic.copy_with_new_subs(subs), p))
+                .filter(|(r, _)| r.sub_value(RelicStat::Spd) > 4.8)
+                .map(|(_, p)| p)
+                .sum::<f64>();
+            assert_float_eq(3.0 / 9.0, p); // 2.6 + 2.6, 2.6 + 2.3 or 2.3 + 2.6
+        }
+    }
+
+    #[test]
+    fn upgrade_splits() {
+        use RelicStat::*;
+
+        // every upgrade picks one of the four lines uniformly, so a split of the upgrades over
+        // the lines is as likely as its share of the 4^k orders the upgrades can come in
+        let lines = [Spd, CritRate, CritDmg, Atk];
+        let relic = Relic::new(5, RelicSlot::Head, Hp);
+        for upgrades in 0..=5 {
+            let mut splits = std::collections::HashMap::new();
+            for order in 0..4usize.pow(upgrades) {
+                let mut counts = [0; 4];
+                let mut rest = order;
+                for _ in 0..upgrades {
+                    counts[rest % 4] += 1;
+                    rest /= 4;
+                }
+                *splits.entry(counts).or_insert(0) += 1;
+            }
+
+            let mut total = 0.0;
+            for (counts, orders) in splits {
+                let rolls = lines.iter().zip(counts).flat_map(|(line, count)| std::iter::repeat_n(*line, count));
+                let p = relic.copy_with_new_subs(lines.into_iter().chain(rolls)).p_sub_u();
+                assert_float_eq(orders as f64 / 4f64.powi(upgrades as i32), p);
+                total += p;
+            }
+            assert_float_eq(1.0, total);
+        }
+
+        // heads only drop with HP and hands with ATK
+        assert_eq!(0.0, Relic::new(5, RelicSlot::Head, Atk).p_main_stat_unchecked());
+        assert_eq!(1.0, Relic::new(5, RelicSlot::Hands, Atk).p_main_stat().unwrap());
+    }
+
+    #[test]
+    fn serde() {
+        let relic = Relic::new(5, RelicSlot::Orb, RelicStat::QuantumDmgBoost)
+            .copy_with_new_subs([SubstatRoll::new(RelicStat::CritDmg, RollTier::High), RelicStat::Spd.into()]);
+        let json = serde_json::to_string(&relic).unwrap();
+        assert!(json.contains(r#""slot":"PlanarSphere","main":"Quantum DMG Boost""#), "{json}");
+        assert_eq!(relic, serde_json::from_str(&json).unwrap());
+
+        // short form, rolls without a tier are mid rolls
+        let short = serde_json::from_str::<Relic>(r#"{
+            "rarity": 5, "slot": "PlanarSphere", "main": "Quantum DMG Boost",
+            "subs": [{"stat": "CRIT DMG", "tier": "High"}, "SPD"]
+        }"#).unwrap();
+        assert_eq!(relic, short);
+
+        assert!(serde_json::from_str::<RelicStat>(r#""CritDmg""#).is_err());
+
+        let relic = relic.set(RelicSet::Glamoth);
+        let json = serde_json::to_string(&relic).unwrap();
+        assert!(json.contains(r#""set":"Firmament Frontline: Glamoth""#), "{json}");
+        assert_eq!(relic, serde_json::from_str(&json).unwrap());
     }
 }
\ No newline at end of file
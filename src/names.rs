@@ -0,0 +1,209 @@
+use std::fmt;
+use std::str::FromStr;
+
+use itertools::Itertools;
+
+use crate::{Relic, RelicSet, RelicSlot, RelicStat};
+
+// every name is matched ignoring case, spaces, `_` and `-`
+const STAT_NAMES: &[(RelicStat, &[&str])] = {
+    use RelicStat::*;
+    &[
+        (Hp, &["HP", "flat HP"]),
+        (Atk, &["ATK", "flat ATK"]),
+        (Def, &["DEF", "flat DEF"]),
+        (HpPercent, &["HP%", "HpPercent"]),
+        (AtkPercent, &["ATK%", "AtkPercent"]),
+        (DefPercent, &["DEF%", "DefPercent"]),
+        (Spd, &["SPD", "speed"]),
+        (CritRate, &["CRIT Rate", "CR"]),
+        (CritDmg, &["CRIT DMG", "CD", "crit damage"]),
+        (EffectHitRate, &["Effect Hit Rate", "EHR"]),
+        (EffectRes, &["Effect RES", "RES", "effect resistance"]),
+        (BreakEffect, &["Break Effect", "BE"]),
+        (EnergyRegenRate, &["Energy Regeneration Rate", "ERR", "EnergyRegenRate", "energy regen"]),
+        (HealingBoost, &["Outgoing Healing Boost", "OHB", "HealingBoost", "healing"]),
+        (PhysDmgBoost, &["Physical DMG Boost", "PhysDmgBoost", "physical", "phys"]),
+        (FireDmgBoost, &["Fire DMG Boost", "FireDmgBoost", "fire"]),
+        (IceDmgBoost, &["Ice DMG Boost", "IceDmgBoost", "ice"]),
+        (WindDmgBoost, &["Wind DMG Boost", "WindDmgBoost", "wind"]),
+        (LightningDmgBoost, &["Lightning DMG Boost", "LightningDmgBoost", "lightning"]),
+        (QuantumDmgBoost, &["Quantum DMG Boost", "QuantumDmgBoost", "quantum"]),
+        (ImaginaryDmgBoost, &["Imaginary DMG Boost", "ImaginaryDmgBoost", "imaginary"]),
+    ]
+};
+
+const SLOT_NAMES: &[(RelicSlot, &[&str])] = {
+    use RelicSlot::*;
+    &[
+        (Head, &["Head"]),
+        (Hands, &["Hands"]),
+        (Body, &["Body"]),
+        (Feet, &["Feet"]),
+        (Orb, &["PlanarSphere", "Orb", "sphere"]),
+        (Rope, &["LinkRope", "Rope"]),
+    ]
+};
+
+fn normalize(name: &str) -> String {
+    name.chars()
+        .filter(|c| !matches!(c, ' ' | '_' | '-'))
+        .flat_map(char::to_lowercase)
+        .collect()
+}
+
+fn lookup<T: Copy>(names: &[(T, &[&str])], name: &str) -> Option<T> {
+    let name = normalize(name);
+    names.iter()
+        .find(|(_, aliases)| aliases.iter().any(|alias| normalize(alias) == name))
+        .map(|(value, _)| *value)
+}
+
+// first name in the table, same as fribbels
+fn canonical<T: PartialEq>(names: &[(T, &'static [&'static str])], value: T) -> &'static str {
+    names.iter()
+        .find(|(v, _)| *v == value)
+        .map(|(_, aliases)| aliases[0])
+        .expect("every value has a name")
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseRelicStatError(String);
+
+impl fmt::Display for ParseRelicStatError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "unknown relic stat `{}`", self.0)
+    }
+}
+
+impl std::error::Error for ParseRelicStatError {}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseRelicSlotError(String);
+
+impl fmt::Display for ParseRelicSlotError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "unknown relic slot `{}`", self.0)
+    }
+}
+
+impl std::error::Error for ParseRelicSlotError {}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseRelicSetError(String);
+
+impl fmt::Display for ParseRelicSetError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "unknown relic set `{}`", self.0)
+    }
+}
+
+impl std::error::Error for ParseRelicSetError {}
+
+/// Parses fribbels names (`CRIT DMG`), short forms (`CD`, `ERR`) and enum names (`CritDmg`)
+impl FromStr for RelicStat {
+    type Err = ParseRelicStatError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        lookup(STAT_NAMES, s)
+            .or_else(|| RelicStat::all().find(|stat| normalize(&format!("{stat:?}")) == normalize(s)))
+            .ok_or_else(|| ParseRelicStatError(s.to_string()))
+    }
+}
+
+/// Fribbels name, e.g. `CRIT DMG`
+impl fmt::Display for RelicStat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.pad(canonical(STAT_NAMES, *self))
+    }
+}
+
+/// Parses fribbels names (`PlanarSphere`, `LinkRope`) and enum names (`Orb`, `Rope`)
+impl FromStr for RelicSlot {
+    type Err = ParseRelicSlotError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        lookup(SLOT_NAMES, s).ok_or_else(|| ParseRelicSlotError(s.to_string()))
+    }
+}
+
+/// Fribbels name, e.g. `PlanarSphere`
+impl fmt::Display for RelicSlot {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.pad(canonical(SLOT_NAMES, *self))
+    }
+}
+
+/// Parses fribbels names (`Genius of Brilliant Stars`) and enum names (`Genius`)
+impl FromStr for RelicSet {
+    type Err = ParseRelicSetError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let name = normalize(s);
+        RelicSet::all()
+            .find(|set| normalize(set.name()) == name || normalize(&format!("{set:?}")) == name)
+            .ok_or_else(|| ParseRelicSetError(s.to_string()))
+    }
+}
+
+/// Fribbels name, e.g. `Genius of Brilliant Stars`
+impl fmt::Display for RelicSet {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.pad(self.name())
+    }
+}
+
+impl Relic {
+    /// Rolls per stat in the order the lines were drawn, e.g. `2x SPD, 1x CRIT DMG`
+    pub fn format_subs(&self) -> String {
+        self.subs.iter()
+            .map(|r| r.stat)
+            .unique()
+            .map(|stat| format!("{}x {stat}", self.sub_rolls(stat)))
+            .join(", ")
+    }
+}
+
+/// e.g. `5* Head HP +15: 2x SPD, 1x CRIT DMG`
+impl fmt::Display for Relic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}* {} {} +{}: {}", self.rarity, self.slot, self.main, self.level, self.format_subs())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn names() {
+        assert_eq!(Ok(RelicStat::CritDmg), "CRIT DMG".parse());
+        assert_eq!(Ok(RelicStat::CritDmg), "cd".parse());
+        assert_eq!(Ok(RelicStat::CritDmg), "CritDmg".parse());
+        assert_eq!(Ok(RelicStat::AtkPercent), "ATK%".parse());
+        assert_eq!(Ok(RelicStat::Atk), "atk".parse());
+        assert_eq!(Ok(RelicStat::EnergyRegenRate), "ERR".parse());
+        assert_eq!(Ok(RelicStat::ImaginaryDmgBoost), "Imaginary DMG Boost".parse());
+        assert_eq!(Err(ParseRelicStatError("crit".to_string())), "crit".parse::<RelicStat>());
+
+        assert_eq!(Ok(RelicSlot::Orb), "PlanarSphere".parse());
+        assert_eq!(Ok(RelicSlot::Rope), "LinkRope".parse());
+        assert_eq!(Ok(RelicSlot::Rope), "rope".parse());
+        assert!("Ring".parse::<RelicSlot>().is_err());
+
+        // display round trips and agrees with serde
+        for stat in RelicStat::all() {
+            assert_eq!(Ok(stat), stat.to_string().parse());
+            assert_eq!(serde_json::to_string(&stat).unwrap(), format!("\"{stat}\""));
+        }
+        for slot in [RelicSlot::Head, RelicSlot::Hands, RelicSlot::Body, RelicSlot::Feet, RelicSlot::Orb, RelicSlot::Rope] {
+            assert_eq!(Ok(slot), slot.to_string().parse());
+            assert_eq!(serde_json::to_string(&slot).unwrap(), format!("\"{slot}\""));
+        }
+
+        let mut relic = Relic::new(5, RelicSlot::Head, RelicStat::Hp)
+            .copy_with_new_subs([RelicStat::Spd, RelicStat::CritDmg, RelicStat::Spd]);
+        relic.level = 3;
+        assert_eq!("5* Head HP +3: 2x SPD, 1x CRIT DMG", relic.to_string());
+    }
+}
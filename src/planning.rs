@@ -0,0 +1,134 @@
+use std::collections::HashMap;
+
+use serde::Serialize;
+
+use crate::distribution::score_key;
+use crate::inventory::roll_score_range;
+use crate::{ConditionalRelicProbabilityCalculator, Domain, DropModel, Inventory, Relic, RelicScorer, RelicSlot};
+
+/// Farming a domain for one of its slots, and what the drops in that slot are worth to each build
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct FarmingTarget {
+    pub domain: Domain,
+    pub slot: RelicSlot,
+    /// Expected score gain per TBP for every build that would take the drop, times its priority
+    pub gains: Vec<(usize, f64)>,
+}
+
+impl FarmingTarget {
+    /// Expected score gain per TBP across every build
+    pub fn gain_per_tbp(&self) -> f64 {
+        self.gains.iter().map(|(_, gain)| gain).sum()
+    }
+}
+
+impl<S: RelicScorer> Inventory<S> {
+    /// Every domain and slot worth farming by the score it is expected to add per TBP, best
+    /// first. A drop counts for each build whose set and main stat it fits, by how much it
+    /// would beat what the build wears once fully leveled.
+    pub fn farming_targets(&self, drop_model: &DropModel) -> Vec<FarmingTarget> {
+        const RARITY: usize = 5;
+        let mut targets: HashMap<(Domain, RelicSlot), Vec<(usize, f64)>> = HashMap::new();
+
+        for (i, build) in self.builds().iter().enumerate().filter(|(_, b)| b.priority > 0.0) {
+            for slot in RelicSlot::all() {
+                let current = self.equipped_score(i, slot);
+                // mains that can't beat the equipped relic don't need a distribution
+                let mains = build.mains(slot).into_iter()
+                    .filter(|main| max_score(&build.scorer, &Relic::new(RARITY, slot, *main))
+                        .is_none_or(|max| score_key(max) > score_key(current)))
+                    .collect::<Vec<_>>();
+                let Some(&main) = mains.first() else { continue };
+
+                // the set is left to each domain below, the substats only depend on the main
+                let relic = Relic::new(RARITY, slot, main);
+                let calculator = ConditionalRelicProbabilityCalculator::new()
+                    .consider_slot()
+                    .consider_main()
+                    .accept_mains(mains);
+                let gain = calculator.calculate_expected_gain(&relic, &build.scorer, current)
+                    .expect("mains are filtered to the slot");
+                if gain <= 0.0 {
+                    continue;
+                }
+
+                for domain in Domain::all().filter(|d| d.slots().contains(&slot)) {
+                    let p_set = if build.sets.is_empty() {
+                        1.0
+                    } else {
+                        let sets = build.sets.iter().copied().filter(|set| domain.sets.contains(set)).collect::<Vec<_>>();
+                        if sets.is_empty() {
+                            continue;
+                        }
+                        calculator.clone().consider_set().accept_sets(sets).p_set(&relic)
+                    };
+                    let per_tbp = build.priority * gain * p_set / drop_model.tbp_per_relic();
+                    targets.entry((domain, slot)).or_default().push((i, per_tbp));
+                }
+            }
+        }
+
+        let mut targets = targets.into_iter()
+            .map(|((domain, slot), gains)| FarmingTarget { domain, slot, gains })
+            .collect::<Vec<_>>();
+        targets.sort_by(|a, b| b.gain_per_tbp().total_cmp(&a.gain_per_tbp())
+            .then_with(|| (a.domain.sets, a.slot as usize).cmp(&(b.domain.sets, b.slot as usize))));
+        targets
+    }
+}
+
+// most a fresh relic can score with every roll the best possible, `None` if the score isn't linear
+fn max_score(scorer: &(impl RelicScorer + ?Sized), relic: &Relic) -> Option<f64> {
+    let linear = scorer.as_linear()?;
+    let (_, best_roll) = roll_score_range(linear, relic)?;
+    let mut leveled = relic.clone();
+    leveled.level = relic.max_level();
+    let rolls = 2 * relic.rarity - 1;
+    let max = scorer.fixed_score(&leveled) + best_roll.max(0.0) * rolls as f64;
+    // keep float noise from ruling out a tie
+    Some(max + 1e-9 * max.abs().max(1.0))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::assert_float_eq;
+    use itertools::Itertools;
+    use crate::{Build, LinearScore, RelicSet, RelicStat};
+    use RelicStat::*;
+
+    #[test]
+    fn farming_targets() {
+        let crit = || LinearScore::roll_weights([(CritRate, 1.0), (CritDmg, 1.0)].into());
+        let relics = HashMap::from([
+            // nothing left to gain on this head
+            ("head".to_string(), Relic::try_new(5, RelicSlot::Head, Hp, 15, [CritRate, CritDmg, Spd, Atk, CritRate, CritRate, CritRate, CritRate, CritRate]).unwrap()),
+        ]);
+        let inventory = Inventory::new(relics, vec![
+            Build::new("genius", crit(), ["head".to_string()])
+                .accept_sets([RelicSet::Genius])
+                .accept_mains(RelicSlot::Body, [CritRate]),
+            Build::new("firesmith", crit(), []).accept_sets([RelicSet::Firesmith]).priority(0.5),
+            Build::new("benched", crit(), []).priority(0.0),
+        ]);
+        let drop_model = DropModel::default();
+        let targets = inventory.farming_targets(&drop_model);
+
+        // genius and firesmith share a domain, the build that takes any set is benched
+        assert!(targets.iter().all(|t| t.domain == RelicSet::Genius.domain()), "{targets:?}");
+        let target = |slot| targets.iter().find(|t| t.slot == slot).unwrap();
+        assert_eq!(None, targets.iter().find(|t| t.slot == RelicSlot::Head && t.gains.iter().any(|(b, _)| *b == 0)));
+        assert_eq!(vec![0, 1], target(RelicSlot::Body).gains.iter().map(|(b, _)| *b).sorted().collect::<Vec<_>>());
+
+        // a crit rate body for genius, any body for firesmith at half the priority
+        let body = |main| Relic::new(5, RelicSlot::Body, main);
+        let calculator = ConditionalRelicProbabilityCalculator::new().consider_set().consider_slot().consider_main();
+        let genius = calculator.clone().accept_sets([RelicSet::Genius]).calculate_expected_gain(&body(CritRate), &crit(), 0.0).unwrap();
+        let firesmith = calculator.accept_sets([RelicSet::Firesmith])
+            .accept_mains(RelicSlot::Body.mains())
+            .calculate_expected_gain(&body(CritRate), &crit(), 0.0)
+            .unwrap();
+        assert_float_eq((genius + 0.5 * firesmith) / drop_model.tbp_per_relic(), target(RelicSlot::Body).gain_per_tbp());
+        assert!(genius < 0.5 * firesmith);
+    }
+}
@@ -1,10 +1,19 @@
-use crate::Relic;
+use itertools::Itertools;
+use serde::{Deserialize, Serialize};
 
-#[derive(Debug, Clone, Default)]
+use crate::{Domain, InvalidRelicError, Relic, RelicScorer, RelicSet, RelicStat, StatTargets};
+
+/// Serializes as e.g. `{"consider_set": true, "consider_slot": true, "consider_main": false,
+/// "accepted_mains": ["CRIT Rate", "CRIT DMG"], "accepted_sets": ["Genius of Brilliant Stars"]}`,
+/// missing keys are false or empty
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
 pub struct ConditionalRelicProbabilityCalculator {
     consider_set: bool,
     consider_slot: bool,
     consider_main: bool,
+    accepted_mains: Vec<RelicStat>,
+    accepted_sets: Vec<RelicSet>,
 }
 
 impl ConditionalRelicProbabilityCalculator {
@@ -21,22 +30,193 @@ impl ConditionalRelicProbabilityCalculator {
         self.consider_main = true;
         self
     }
+    /// Any of `mains` will do instead of only the relic's own main stat. Without
+    /// [`Self::consider_main`] the result is conditioned on rolling one of them.
+    pub fn accept_mains(mut self, mains: impl IntoIterator<Item=RelicStat>) -> Self {
+        self.accepted_mains = mains.into_iter().collect();
+        self
+    }
 
-    pub fn calculate_for_relic(&self, relic: &Relic, filter: impl FnMut(&Relic) -> bool) -> f64 {
-        let mut p = relic.filtered_p_sub(filter);
+    /// Fails if the relic's rarity, slot and an accepted main stat can't drop together.
+    /// `filter` sees the main stat of each accepted main, so it can differ between them.
+    pub fn calculate_for_relic(&self, relic: &Relic, mut filter: impl FnMut(&Relic) -> bool) -> Result<f64, InvalidRelicError> {
+        let p_subs = self.mains(relic)?.iter()
+            .map(|relic| Ok((relic.p_main_stat_unchecked(), relic.filtered_p_sub(&mut filter)?)))
+            .collect::<Result<Vec<_>, InvalidRelicError>>()?;
+        Ok(self.condition(relic, &p_subs))
+    }
+
+    /// Same as [`Self::calculate_for_relic`] with a `score >= min_score` filter, looked up in
+    /// the score distribution
+    pub fn calculate_for_score(&self, relic: &Relic, score: &(impl RelicScorer + ?Sized), min_score: f64) -> Result<f64, InvalidRelicError> {
+        let p_subs = self.mains(relic)?.iter()
+            .map(|relic| Ok((relic.p_main_stat_unchecked(), relic.score_distribution(score)?.p_at_least(min_score))))
+            .collect::<Result<Vec<_>, InvalidRelicError>>()?;
+        Ok(self.condition(relic, &p_subs))
+    }
+
+    /// Expected score gain over `current_score` of a drop, lower scores count as no gain.
+    /// Weighted the same way as the probabilities, e.g. by the chance of the slot and main stat.
+    pub fn calculate_expected_gain(&self, relic: &Relic, score: &(impl RelicScorer + ?Sized), current_score: f64) -> Result<f64, InvalidRelicError> {
+        let gains = self.mains(relic)?.iter()
+            .map(|relic| Ok((relic.p_main_stat_unchecked(), relic.score_distribution(score)?.expected_gain(current_score))))
+            .collect::<Result<Vec<_>, InvalidRelicError>>()?;
+        Ok(self.condition(relic, &gains))
+    }
+
+    /// Probability of a drop that meets every target once fully leveled, along with `others`,
+    /// the relics the build keeps in its other slots. The drop's main stat counts too.
+    pub fn calculate_for_targets<'a>(&self, relic: &Relic, targets: &StatTargets, others: impl IntoIterator<Item=&'a Relic>) -> Result<f64, InvalidRelicError> {
+        let missing = targets.missing(others);
+        let p_subs = self.mains(relic)?.iter()
+            .map(|relic| {
+                let main = relic.main.main_value(relic.rarity, relic.max_level());
+                let subs = missing.iter()
+                    .map(|(stat, missing)| (*stat, if *stat == relic.main { missing - main } else { *missing }))
+                    .collect::<Vec<_>>();
+                Ok((relic.p_main_stat_unchecked(), relic.p_sub_at_least(&subs)?))
+            })
+            .collect::<Result<Vec<_>, InvalidRelicError>>()?;
+        Ok(self.condition(relic, &p_subs))
+    }
+
+    /// Sets that will do for [`Self::consider_set`], farmed in the domain that drops the most
+    /// of them. Without any the relic has to be one specific set of its domain.
+    pub fn accept_sets(mut self, sets: impl IntoIterator<Item=RelicSet>) -> Self {
+        self.accepted_sets = sets.into_iter().collect();
+        self
+    }
+
+    /// Probability of a drop being an accepted set. Only sets that drop in the relic's slot
+    /// count, so it is 0 when none of them do.
+    pub fn p_set(&self, relic: &Relic) -> f64 {
+        if self.accepted_sets.is_empty() {
+            return relic.p_main_set();
+        }
+        let sets = self.sets_for(relic);
+        Domain::best_for(&sets).map_or(0.0, |domain| domain.p_accepted(&sets))
+    }
+
+    /// Domain the relic is farmed in when the set is considered, the best one for the accepted
+    /// sets of its slot or else the one of the relic's own set
+    pub fn domain(&self, relic: &Relic) -> Option<Domain> {
+        if !self.consider_set {
+            return None;
+        }
+        if self.accepted_sets.is_empty() {
+            return relic.set.map(|set| set.domain());
+        }
+        Domain::best_for(&self.sets_for(relic))
+    }
+
+    // accepted sets that drop in the relic's slot
+    fn sets_for(&self, relic: &Relic) -> Vec<RelicSet> {
+        self.accepted_sets.iter()
+            .copied()
+            .filter(|set| set.slots().contains(&relic.slot))
+            .collect()
+    }
+
+    // the relic once per accepted main stat, each has its own substat pool
+    fn mains(&self, relic: &Relic) -> Result<Vec<Relic>, InvalidRelicError> {
+        if self.accepted_mains.is_empty() {
+            relic.validate_kind()?;
+            return Ok(vec![relic.clone()]);
+        }
+        self.accepted_mains.iter()
+            .unique()
+            .map(|&main| {
+                let relic = Relic { set: relic.set, ..Relic::new(relic.rarity, relic.slot, main) };
+                relic.validate_kind()?;
+                Ok(relic)
+            })
+            .collect()
+    }
+
+    // `p_subs` has the main stat and substat probability of every accepted main
+    fn condition(&self, relic: &Relic, p_subs: &[(f64, f64)]) -> f64 {
+        let p_main_stat = p_subs.iter().map(|(p_main, _)| p_main).sum::<f64>();
+        let mut p = p_subs.iter().map(|(p_main, p_sub)| p_main * p_sub).sum::<f64>();
 
         if self.consider_set {
-            p *= relic.p_main_set();
+            p *= self.p_set(relic);
         }
 
         if self.consider_slot {
             p *= relic.p_main_slot();
         }
 
-        if self.consider_main {
-            p *= relic.p_main_stat();
+        if !self.consider_main {
+            p /= p_main_stat;
         }
-        
+
         p
     }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::assert_float_eq;
+    use crate::{LinearScore, RelicSlot};
+    use RelicStat::*;
+
+    #[test]
+    fn accepted_mains() {
+        let crit = LinearScore::roll_weights([(CritRate, 1.0), (CritDmg, 1.0)].into());
+        let body = |main| Relic::new(3, RelicSlot::Body, main);
+        let p_sub = |main| body(main).score_distribution(&crit).unwrap().p_at_least(2.0);
+        let calculator = ConditionalRelicProbabilityCalculator::new().consider_main();
+
+        // a single main is the same as before
+        assert_float_eq(
+            body(CritDmg).p_main_stat().unwrap() * p_sub(CritDmg),
+            calculator.calculate_for_score(&body(CritDmg), &crit, 2.0).unwrap(),
+        );
+
+        let either = calculator.clone().accept_mains([CritRate, CritDmg]);
+        let p = body(CritRate).p_main_stat().unwrap() * p_sub(CritRate) + body(CritDmg).p_main_stat().unwrap() * p_sub(CritDmg);
+        assert_float_eq(p, either.calculate_for_score(&body(CritDmg), &crit, 2.0).unwrap());
+        assert_float_eq(p, either.calculate_for_relic(&body(CritDmg), |r| crit.score(r) >= 2.0).unwrap());
+
+        // conditioned on rolling one of them, crit rate and crit dmg bodies are equally likely
+        let given_either = ConditionalRelicProbabilityCalculator::new().accept_mains([CritRate, CritDmg]);
+        assert_float_eq(
+            (p_sub(CritRate) + p_sub(CritDmg)) / 2.0,
+            given_either.calculate_for_score(&body(CritDmg), &crit, 2.0).unwrap(),
+        );
+
+        // the filter sees which main it's looking at
+        assert_float_eq(
+            body(CritRate).p_main_stat().unwrap() * p_sub(CritRate),
+            either.calculate_for_relic(&body(CritDmg), |r| r.main == CritRate && crit.score(r) >= 2.0).unwrap(),
+        );
+
+        assert!(either.clone().accept_mains([CritDmg, Spd]).calculate_for_score(&body(CritDmg), &crit, 2.0).is_err());
+    }
+
+    #[test]
+    fn accepted_sets() {
+        let relic = Relic::new(3, RelicSlot::Head, Hp);
+        let set = ConditionalRelicProbabilityCalculator::new().consider_set();
+        let p = |calculator: &ConditionalRelicProbabilityCalculator| calculator.calculate_for_relic(&relic, |_| true).unwrap();
+
+        assert_float_eq(0.5, p(&set));
+        assert_float_eq(0.5, p(&set.clone().accept_sets([RelicSet::Genius])));
+        // both sets of a domain, e.g. a character that can use either
+        assert_float_eq(1.0, p(&set.clone().accept_sets([RelicSet::Genius, RelicSet::Firesmith])));
+        // 2+2 from different domains only gets half of either
+        assert_float_eq(0.5, p(&set.clone().accept_sets([RelicSet::Genius, RelicSet::Musketeer])));
+        assert_float_eq(1.0, p(&set.clone().accept_sets([RelicSet::Genius, RelicSet::Musketeer, RelicSet::Passerby])));
+
+        // planar sets never drop as heads
+        assert_float_eq(0.0, p(&set.clone().accept_sets([RelicSet::Glamoth, RelicSet::Penacony])));
+        assert_float_eq(0.5, p(&set.clone().accept_sets([RelicSet::Genius, RelicSet::Glamoth, RelicSet::Penacony])));
+        assert_eq!(None, set.clone().accept_sets([RelicSet::Glamoth]).domain(&relic));
+        assert_eq!(Some(RelicSet::Genius.domain()), set.clone().accept_sets([RelicSet::Genius, RelicSet::Glamoth, RelicSet::Penacony]).domain(&relic));
+
+        assert_eq!(None, ConditionalRelicProbabilityCalculator::new().domain(&relic.clone().set(RelicSet::Genius)));
+        assert_eq!(Some(RelicSet::Genius.domain()), set.domain(&relic.clone().set(RelicSet::Genius)));
+        assert_eq!(Some(RelicSet::Musketeer.domain()), set.accept_sets([RelicSet::Musketeer]).domain(&relic.set(RelicSet::Genius)));
+    }
 }
\ No newline at end of file
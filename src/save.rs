@@ -0,0 +1,387 @@
+use std::collections::HashMap;
+use std::fmt;
+
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::{Relic, RelicSlot, RelicStat, RollTier, SubstatRoll};
+
+/// Save file of the fribbels optimizer, with only the parts this crate reads
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct FribbelsSave {
+    #[serde(default)]
+    pub relics: Vec<SaveRelic>,
+    #[serde(default)]
+    pub characters: Vec<SaveCharacter>,
+    /// Stat weights set in the relic scorer, by character id
+    #[serde(default, rename = "scoringMetadataOverrides")]
+    pub scoring_metadata_overrides: HashMap<String, ScoringOverride>,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct SaveRelic {
+    pub id: String,
+    /// Rarity
+    pub grade: usize,
+    pub part: String,
+    #[serde(default)]
+    pub set: Option<String>,
+    #[serde(default)]
+    pub enhance: usize,
+    pub main: SaveMainStat,
+    #[serde(default)]
+    pub substats: Vec<SaveSubstat>,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct SaveMainStat {
+    pub stat: String,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct SaveSubstat {
+    pub stat: String,
+    pub value: f64,
+    /// Upgrades that went into this stat, missing in older saves
+    #[serde(default, rename = "addedRolls")]
+    pub added_rolls: Option<usize>,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct SaveCharacter {
+    pub id: String,
+    /// Relic id by part, parts without a relic are null or missing
+    #[serde(default)]
+    pub equipped: HashMap<String, Option<String>>,
+    #[serde(default)]
+    pub form: Option<SaveForm>,
+}
+
+/// Optimizer tab settings of a character
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct SaveForm {
+    #[serde(default)]
+    pub weights: HashMap<String, Value>,
+    /// Main stat filters, empty or missing for any main stat
+    #[serde(default, rename = "mainBody")]
+    pub main_body: Option<Vec<String>>,
+    #[serde(default, rename = "mainFeet")]
+    pub main_feet: Option<Vec<String>>,
+    #[serde(default, rename = "mainPlanarSphere")]
+    pub main_planar_sphere: Option<Vec<String>>,
+    #[serde(default, rename = "mainLinkRope")]
+    pub main_link_rope: Option<Vec<String>>,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct ScoringOverride {
+    #[serde(default)]
+    pub stats: HashMap<String, Value>,
+}
+
+/// A value read from the save along with everything that had to be skipped
+#[derive(Clone, Debug, PartialEq)]
+pub struct Imported<T> {
+    pub value: T,
+    pub warnings: Vec<String>,
+}
+
+// the top level is read first so errors can name the relic or character that failed
+#[derive(Deserialize)]
+struct RawSave {
+    #[serde(default)]
+    relics: Vec<Value>,
+    #[serde(default)]
+    characters: Vec<Value>,
+    #[serde(default, rename = "scoringMetadataOverrides")]
+    scoring_metadata_overrides: HashMap<String, Value>,
+}
+
+impl FribbelsSave {
+    pub fn from_json(json: &str) -> Result<Self, ParseFribbelsSaveError> {
+        let raw = serde_json::from_str::<RawSave>(json)
+            .map_err(|e| ParseFribbelsSaveError(format!("save: {e}")))?;
+
+        Ok(Self {
+            relics: parse_all("relic", raw.relics)?,
+            characters: parse_all("character", raw.characters)?,
+            scoring_metadata_overrides: raw.scoring_metadata_overrides.into_iter()
+                .map(|(id, value)| {
+                    let scoring = serde_json::from_value(value)
+                        .map_err(|e| ParseFribbelsSaveError(format!("scoring override of character {id}: {e}")))?;
+                    Ok((id, scoring))
+                })
+                .collect::<Result<_, _>>()?,
+        })
+    }
+
+    /// Every relic that could be read, by id
+    pub fn relics(&self) -> Imported<HashMap<String, Relic>> {
+        let mut warnings = vec![];
+        let relics = self.relics.iter()
+            .filter_map(|relic| match relic.to_relic() {
+                Ok(imported) => {
+                    warnings.extend(imported.warnings);
+                    Some((relic.id.clone(), imported.value))
+                }
+                Err(e) => {
+                    warnings.push(format!("skipped {e}"));
+                    None
+                }
+            })
+            .collect();
+        Imported { value: relics, warnings }
+    }
+
+    pub fn character(&self, id: u32) -> Option<&SaveCharacter> {
+        self.characters.iter().find(|c| c.id() == Some(id))
+    }
+
+    /// Weights set for the character in the relic scorer
+    pub fn scoring_weights(&self, id: u32) -> Option<Imported<HashMap<RelicStat, f64>>> {
+        self.scoring_metadata_overrides.get(&id.to_string())
+            .map(|scoring| parse_weights(&scoring.stats))
+    }
+}
+
+impl SaveRelic {
+    /// Unknown sets are left out with a warning, anything else unknown is an error
+    pub fn to_relic(&self) -> Result<Imported<Relic>, ParseFribbelsSaveError> {
+        let error = |message: String| ParseFribbelsSaveError(format!("relic {}: {message}", self.id));
+        let mut warnings = vec![];
+
+        let slot = self.part.parse().map_err(|e| error(format!("{e}")))?;
+        let main = self.main.stat.parse().map_err(|e| error(format!("{e}")))?;
+        let set = match self.set.as_deref().map(str::parse) {
+            Some(Ok(set)) => Some(set),
+            Some(Err(e)) => {
+                warnings.push(format!("relic {}: {e}, the set is left unknown", self.id));
+                None
+            }
+            None => None,
+        };
+
+        // the first roll of every stat is one of its lines, the rest are upgrades after them
+        let mut subs = vec![];
+        let mut upgrades = vec![];
+        for sub in &self.substats {
+            let stat = sub.stat.parse::<RelicStat>().map_err(|e| error(format!("{e}")))?;
+            let rolls = sub.added_rolls.map_or_else(|| guess_rolls(stat, self.grade, sub.value), |added| added + 1);
+            let mut rolls = SubstatRoll::from_value(stat, self.grade, rolls, sub.value).into_iter();
+            subs.extend(rolls.next());
+            upgrades.extend(rolls);
+        }
+        subs.extend(upgrades);
+
+        let relic = Relic { rarity: self.grade, slot, main, set, level: self.enhance, subs };
+        Ok(Imported { value: relic, warnings })
+    }
+}
+
+impl SaveCharacter {
+    /// Numeric id, as used by the game
+    pub fn id(&self) -> Option<u32> {
+        self.id.parse().ok()
+    }
+
+    /// Ids of the equipped relics
+    pub fn equipped(&self) -> impl Iterator<Item=&str> {
+        self.equipped.values().flatten().map(String::as_str)
+    }
+
+    /// Weights set for the character in the optimizer tab
+    pub fn form_weights(&self) -> Option<Imported<HashMap<RelicStat, f64>>> {
+        self.form.as_ref().map(|form| parse_weights(&form.weights))
+    }
+
+    /// Main stats the optimizer tab allows, for the slots that are filtered
+    pub fn form_mains(&self) -> Imported<HashMap<RelicSlot, Vec<RelicStat>>> {
+        let mut warnings = vec![];
+        let mut mains = HashMap::new();
+        let Some(form) = &self.form else {
+            return Imported { value: mains, warnings };
+        };
+
+        let filters = [
+            (RelicSlot::Body, &form.main_body),
+            (RelicSlot::Feet, &form.main_feet),
+            (RelicSlot::Orb, &form.main_planar_sphere),
+            (RelicSlot::Rope, &form.main_link_rope),
+        ];
+        for (slot, filter) in filters {
+            let stats = filter.iter().flatten()
+                .filter_map(|stat| stat.parse::<RelicStat>()
+                    .map_err(|e| warnings.push(format!("{e} in the {slot} main stat filter")))
+                    .ok())
+                .collect::<Vec<_>>();
+            if !stats.is_empty() {
+                mains.insert(slot, stats);
+            }
+        }
+        Imported { value: mains, warnings }
+    }
+}
+
+/// Stat weights keyed by fribbels stat names. Keys that aren't stats are left out, with a
+/// warning unless they are known optimizer settings.
+pub fn parse_weights(pairs: &HashMap<String, Value>) -> Imported<HashMap<RelicStat, f64>> {
+    const SETTINGS: [&str; 3] = ["headHands", "bodyFeet", "sphereRope"];
+
+    let mut warnings = vec![];
+    let mut weights = HashMap::new();
+    for (key, value) in pairs {
+        match (key.parse::<RelicStat>(), value.as_f64()) {
+            (Ok(stat), Some(weight)) => {
+                weights.insert(stat, weight);
+            }
+            (Ok(stat), None) => warnings.push(format!("weight of {stat} is not a number: {value}")),
+            (Err(e), _) if !SETTINGS.contains(&key.as_str()) => warnings.push(format!("{e} in weights")),
+            (Err(_), _) => {}
+        }
+    }
+    Imported { value: weights, warnings }
+}
+
+/// Base HP, ATK and DEF by character id, from the optimizer's character data, e.g.
+/// `{"1005": {"HP": 1086.624, "ATK": 679.14, "DEF": 485.1, "SPD": 100}}`. Other stats are
+/// only checked to be numbers.
+pub fn parse_base_stats(json: &str) -> Result<HashMap<u32, (f64, f64, f64)>, ParseBaseStatsError> {
+    let characters: HashMap<String, HashMap<String, Value>> = serde_json::from_str(json)
+        .map_err(|e| ParseBaseStatsError(format!("{e}")))?;
+
+    let mut base_stats = HashMap::new();
+    for (id, stats) in characters {
+        let error = |message: String| ParseBaseStatsError(format!("character {id}: {message}"));
+        let char_id = id.parse::<u32>().map_err(|_| error("the id is not a number".to_string()))?;
+        if let Some((stat, value)) = stats.iter().find(|(_, value)| value.as_f64().is_none()) {
+            return Err(error(format!("{stat} is not a number: {value}")));
+        }
+
+        let stat = |name: &str| stats.get(name)
+            .and_then(Value::as_f64)
+            .filter(|value| *value > 0.0)
+            .ok_or_else(|| error(format!("no positive {name}")));
+        base_stats.insert(char_id, (stat("HP")?, stat("ATK")?, stat("DEF")?));
+    }
+    Ok(base_stats)
+}
+
+fn parse_all<T: DeserializeOwned>(kind: &str, values: Vec<Value>) -> Result<Vec<T>, ParseFribbelsSaveError> {
+    values.into_iter()
+        .enumerate()
+        .map(|(i, value)| {
+            let id = value.get("id").map_or_else(|| "without id".to_string(), |id| format!("id {id}"));
+            serde_json::from_value(value)
+                .map_err(|e| ParseFribbelsSaveError(format!("{kind} {i} ({id}): {e}")))
+        })
+        .collect()
+}
+
+// older saves only have the total, mid rolls are the average
+fn guess_rolls(stat: RelicStat, rarity: usize, value: f64) -> usize {
+    let mid = stat.sub_roll_value(rarity, RollTier::Mid);
+    if mid > 0.0 { (value / mid).round().clamp(1.0, 6.0) as usize } else { 1 }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseFribbelsSaveError(String);
+
+impl fmt::Display for ParseFribbelsSaveError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "invalid fribbels save: {}", self.0)
+    }
+}
+
+impl std::error::Error for ParseFribbelsSaveError {}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseBaseStatsError(String);
+
+impl fmt::Display for ParseBaseStatsError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "invalid base stats: {}", self.0)
+    }
+}
+
+impl std::error::Error for ParseBaseStatsError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::RelicSet;
+    use RelicStat::*;
+
+    #[test]
+    fn fribbels_save() {
+        let save = FribbelsSave::from_json(r#"{
+            "relics": [
+                {"id": "a", "grade": 5, "part": "Head", "set": "Genius of Brilliant Stars", "enhance": 3,
+                 "main": {"stat": "HP", "value": 270},
+                 "substats": [{"stat": "CRIT DMG", "value": 12.96, "addedRolls": 1}, {"stat": "SPD", "value": 2.3}, {"stat": "ATK%", "value": 3.888}, {"stat": "DEF", "value": 19.05}]},
+                {"id": "b", "grade": 5, "part": "Body", "set": "Some Future Set", "main": {"stat": "CRIT Rate"}, "substats": []},
+                {"id": "c", "grade": 5, "part": "Body", "main": {"stat": "Crit Luck"}},
+                {"id": "d", "grade": 5, "part": "Feet", "enhance": 15, "main": {"stat": "SPD"},
+                 "substats": [{"stat": "CRIT Rate", "value": 8.748, "addedRolls": 2}, {"stat": "CRIT DMG", "value": 5.832, "addedRolls": 0},
+                              {"stat": "ATK%", "value": 7.776, "addedRolls": 1}, {"stat": "DEF", "value": 57.15588, "addedRolls": 2}]}
+            ],
+            "characters": [{"id": "1005", "equipped": {"Head": "a", "Hands": null}, "form": {"weights": {"SPD": 1, "headHands": 0, "Luck": 1}, "mainBody": ["CRIT Rate", "CRIT DMG"], "mainFeet": []}}],
+            "scoringMetadataOverrides": {"1005": {"stats": {"CRIT DMG": 1, "ATK%": 0.75}}}
+        }"#).unwrap();
+
+        let relics = save.relics();
+        assert_eq!(3, relics.value.len());
+        assert_eq!(2, relics.warnings.len(), "{:?}", relics.warnings);
+        assert!(relics.warnings.iter().any(|w| w.contains("relic c") && w.contains("Crit Luck")));
+
+        let head = &relics.value["a"];
+        assert_eq!((RelicSlot::Head, Some(RelicSet::Genius), 3), (head.slot, head.set, head.level));
+        assert_eq!(2, head.sub_rolls(RelicStat::CritDmg));
+        assert_eq!(1, head.sub_rolls(RelicStat::Spd));
+        assert!(head.validate().is_ok());
+        assert_eq!(None, relics.value["b"].set);
+
+        // lines come before upgrades, like on a relic built by hand
+        let feet = &relics.value["d"];
+        assert_eq!(vec![CritRate, CritDmg, AtkPercent, Def], feet.subs.iter().take(4).map(|s| s.stat).collect::<Vec<_>>());
+        let mut by_hand = Relic::new(5, RelicSlot::Feet, Spd)
+            .copy_with_new_subs([CritRate, CritDmg, AtkPercent, Def, CritRate, CritRate, AtkPercent, Def, Def]);
+        by_hand.level = 15;
+        assert_eq!(by_hand.p_sub().unwrap(), feet.p_sub().unwrap());
+
+        let character = save.character(1005).unwrap();
+        assert_eq!(vec!["a"], character.equipped().collect::<Vec<_>>());
+        let form = character.form_weights().unwrap();
+        assert_eq!(HashMap::from([(RelicStat::Spd, 1.0)]), form.value);
+        assert_eq!(1, form.warnings.len());
+        let mains = character.form_mains();
+        assert_eq!(HashMap::from([(RelicSlot::Body, vec![RelicStat::CritRate, RelicStat::CritDmg])]), mains.value);
+        assert_eq!(Some(0.75), save.scoring_weights(1005).unwrap().value.get(&RelicStat::AtkPercent).copied());
+
+        // saves without scorer overrides read the same either way
+        let json = r#"{"relics": [], "characters": []}"#;
+        assert_eq!(FribbelsSave::from_json(json).unwrap(), serde_json::from_str::<FribbelsSave>(json).unwrap());
+
+        // errors say what failed
+        let error = FribbelsSave::from_json(r#"{"relics": [{"id": "x", "part": "Head"}]}"#).unwrap_err();
+        assert!(error.to_string().contains("relic 0 (id \"x\")"), "{error}");
+        assert!(error.to_string().contains("grade"), "{error}");
+    }
+
+    #[test]
+    fn base_stats() {
+        let stats = parse_base_stats(r#"{"1005": {"HP": 1086.624, "ATK": 679.14, "DEF": 485.1, "SPD": 100}}"#).unwrap();
+        assert_eq!(HashMap::from([(1005, (1086.624, 679.14, 485.1))]), stats);
+
+        for json in [
+            r#"{"Kafka": {"HP": 1086.624, "ATK": 679.14, "DEF": 485.1}}"#,
+            r#"{"1005": {"HP": 1086.624, "ATK": "679", "DEF": 485.1}}"#,
+            r#"{"1005": {"HP": 1086.624, "ATK": 679.14, "DEF": 485.1, "SPD": null}}"#,
+            r#"{"1005": {"HP": 1086.624, "ATK": 679.14}}"#,
+            r#"{"1005": {"HP": 0, "ATK": 679.14, "DEF": 485.1}}"#,
+            r#"{"1005": [1086.624, 679.14, 485.1]}"#,
+        ] {
+            assert!(parse_base_stats(json).is_err(), "{json}");
+        }
+    }
+}
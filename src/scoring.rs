@@ -0,0 +1,73 @@
+use crate::{LinearScore, Relic, RelicStat};
+
+/// Scores a relic, higher is better. Any `Fn(&Relic) -> f64` is a scorer.
+pub trait RelicScorer {
+    fn score(&self, relic: &Relic) -> f64;
+
+    /// The score as a sum over rolls plus [`Self::fixed_score`], if it is one. Distributions of
+    /// linear scores are computed exactly instead of enumerating every relic.
+    fn as_linear(&self) -> Option<&LinearScore> {
+        None
+    }
+
+    /// Part of a linear score that doesn't depend on the substats, e.g. for the main stat
+    fn fixed_score(&self, _relic: &Relic) -> f64 {
+        0.0
+    }
+}
+
+impl RelicScorer for LinearScore {
+    fn score(&self, relic: &Relic) -> f64 {
+        LinearScore::score(self, relic)
+    }
+
+    fn as_linear(&self) -> Option<&LinearScore> {
+        Some(self)
+    }
+}
+
+impl<F: Fn(&Relic) -> f64> RelicScorer for F {
+    fn score(&self, relic: &Relic) -> f64 {
+        self(relic)
+    }
+}
+
+impl LinearScore {
+    /// Crit value, 2x CRIT Rate + CRIT DMG
+    pub fn crit_value() -> Self {
+        Self::value_weights([(RelicStat::CritRate, 2.0), (RelicStat::CritDmg, 1.0)].into())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::assert_float_eq;
+    use crate::{RelicSlot, RollTier, SubstatRoll};
+    use RelicStat::*;
+
+    #[test]
+    fn scorers() {
+        let relic = Relic::new(5, RelicSlot::Head, Hp).copy_with_new_subs([
+            SubstatRoll::new(CritRate, RollTier::High),
+            SubstatRoll::new(CritDmg, RollTier::High),
+            SubstatRoll::new(Atk, RollTier::Low),
+        ]);
+        assert_float_eq(2.0 * 3.24 + 6.48, LinearScore::crit_value().score(&relic));
+
+        // closures are scorers too, without the exact distribution
+        let crit = LinearScore::roll_weights([(CritRate, 1.0), (CritDmg, 1.0)].into());
+        let closure = |r: &Relic| (r.sub_rolls(CritRate) + r.sub_rolls(CritDmg)) as f64;
+        assert_float_eq(2.0, RelicScorer::score(&closure, &relic));
+        assert!(closure.as_linear().is_none());
+
+        let relic = Relic::new(3, RelicSlot::Body, CritRate);
+        let exact = relic.score_distribution(&crit).unwrap();
+        let enumerated = relic.score_distribution(&closure).unwrap();
+        assert_eq!(exact.points().len(), enumerated.points().len());
+        for (a, b) in exact.points().iter().zip(enumerated.points()) {
+            assert_float_eq(a.0, b.0);
+            assert_float_eq(a.1, b.1);
+        }
+    }
+}
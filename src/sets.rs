@@ -0,0 +1,325 @@
+use serde::{Deserialize, Serialize};
+
+use crate::{GameTables, RelicSlot};
+
+/// Relic and ornament sets, serialized with fribbels names
+#[derive(PartialEq, Eq, Hash, Ord, PartialOrd, Copy, Clone, Debug, Serialize, Deserialize)]
+pub enum RelicSet {
+    // cavern relics
+    #[serde(rename = "Passerby of Wandering Cloud")]
+    Passerby,
+    #[serde(rename = "Musketeer of Wild Wheat")]
+    Musketeer,
+    #[serde(rename = "Knight of Purity Palace")]
+    Knight,
+    #[serde(rename = "Hunter of Glacial Forest")]
+    Hunter,
+    #[serde(rename = "Champion of Streetwise Boxing")]
+    Champion,
+    #[serde(rename = "Guard of Wuthering Snow")]
+    Guard,
+    #[serde(rename = "Firesmith of Lava-Forging")]
+    Firesmith,
+    #[serde(rename = "Genius of Brilliant Stars")]
+    Genius,
+    #[serde(rename = "Band of Sizzling Thunder")]
+    Band,
+    #[serde(rename = "Eagle of Twilight Line")]
+    Eagle,
+    #[serde(rename = "Thief of Shooting Meteor")]
+    Thief,
+    #[serde(rename = "Wastelander of Banditry Desert")]
+    Wastelander,
+    #[serde(rename = "Longevous Disciple")]
+    LongevousDisciple,
+    #[serde(rename = "Messenger Traversing Hackerspace")]
+    Messenger,
+    #[serde(rename = "The Ashblazing Grand Duke")]
+    GrandDuke,
+    #[serde(rename = "Prisoner in Deep Confinement")]
+    Prisoner,
+    #[serde(rename = "Pioneer Diver of Dead Waters")]
+    PioneerDiver,
+    #[serde(rename = "Watchmaker, Master of Dream Machinations")]
+    Watchmaker,
+    #[serde(rename = "Iron Cavalry Against the Scourge")]
+    IronCavalry,
+    #[serde(rename = "The Wind-Soaring Valorous")]
+    WindSoaring,
+    #[serde(rename = "Sacerdos' Relived Ordeal")]
+    Sacerdos,
+    #[serde(rename = "Scholar Lost in Erudition")]
+    Scholar,
+    #[serde(rename = "Hero of Triumphant Song")]
+    Hero,
+    #[serde(rename = "Poet of Mourning Collapse")]
+    Poet,
+    #[serde(rename = "Warrior Goddess of Sun and Thunder")]
+    WarriorGoddess,
+    #[serde(rename = "Wavestrider Captain")]
+    Wavestrider,
+    #[serde(rename = "World-Remaking Deliverer")]
+    Deliverer,
+    #[serde(rename = "Self-Enshrouded Recluse")]
+    Recluse,
+
+    // planar ornaments
+    #[serde(rename = "Space Sealing Station")]
+    SpaceSealingStation,
+    #[serde(rename = "Fleet of the Ageless")]
+    FleetOfTheAgeless,
+    #[serde(rename = "Pan-Cosmic Commercial Enterprise")]
+    PanCosmic,
+    #[serde(rename = "Belobog of the Architects")]
+    Belobog,
+    #[serde(rename = "Celestial Differentiator")]
+    CelestialDifferentiator,
+    #[serde(rename = "Inert Salsotto")]
+    InertSalsotto,
+    #[serde(rename = "Talia: Kingdom of Banditry")]
+    Talia,
+    #[serde(rename = "Sprightly Vonwacq")]
+    Vonwacq,
+    #[serde(rename = "Rutilant Arena")]
+    RutilantArena,
+    #[serde(rename = "Broken Keel")]
+    BrokenKeel,
+    #[serde(rename = "Firmament Frontline: Glamoth")]
+    Glamoth,
+    #[serde(rename = "Penacony, Land of the Dreams")]
+    Penacony,
+    #[serde(rename = "Sigonia, the Unclaimed Desolation")]
+    Sigonia,
+    #[serde(rename = "Izumo Gensei and Takama Divine Realm")]
+    Izumo,
+    #[serde(rename = "Duran, Dynasty of Running Wolves")]
+    Duran,
+    #[serde(rename = "Forge of the Kalpagni Lantern")]
+    Kalpagni,
+    #[serde(rename = "Lushaka, the Sunken Seas")]
+    Lushaka,
+    #[serde(rename = "The Wondrous BananAmusement Park")]
+    BananAmusementPark,
+    #[serde(rename = "Bone Collection's Serene Demesne")]
+    BoneCollection,
+    #[serde(rename = "Giant Tree of Rapt Brooding")]
+    GiantTree,
+    #[serde(rename = "Arcadia of Woven Dreams")]
+    Arcadia,
+    #[serde(rename = "Revelry by the Sea")]
+    Revelry,
+}
+
+/// Whether a set drops from the cavern of corrosion or the simulated universe
+#[derive(PartialEq, Eq, Hash, Copy, Clone, Debug, Serialize, Deserialize)]
+pub enum SetKind {
+    /// Head, hands, body and feet, with 2 and 4 piece bonuses
+    Cavern,
+    /// Planar sphere and link rope, with a 2 piece bonus only
+    Planar,
+}
+
+/// Catalog entry of a set. The bonuses are short summaries, not the in-game text.
+#[derive(PartialEq, Eq, Debug)]
+pub struct SetInfo {
+    pub set: RelicSet,
+    /// Fribbels name, same as the in-game one
+    pub name: &'static str,
+    pub kind: SetKind,
+    pub two_piece: &'static str,
+    pub four_piece: Option<&'static str>,
+}
+
+const SETS: &[SetInfo] = {
+    use RelicSet::*;
+    use SetKind::*;
+    const fn cavern(set: RelicSet, name: &'static str, two_piece: &'static str, four_piece: &'static str) -> SetInfo {
+        SetInfo { set, name, kind: Cavern, two_piece, four_piece: Some(four_piece) }
+    }
+    const fn planar(set: RelicSet, name: &'static str, two_piece: &'static str) -> SetInfo {
+        SetInfo { set, name, kind: Planar, two_piece, four_piece: None }
+    }
+    &[
+        cavern(Passerby, "Passerby of Wandering Cloud", "Outgoing Healing +10%", "regenerates 1 Skill Point at the start of battle"),
+        cavern(Musketeer, "Musketeer of Wild Wheat", "ATK +12%", "SPD +6%, Basic ATK DMG +10%"),
+        cavern(Knight, "Knight of Purity Palace", "DEF +15%", "shields +20%"),
+        cavern(Hunter, "Hunter of Glacial Forest", "Ice DMG +10%", "CRIT DMG +25% for 2 turns after an Ultimate"),
+        cavern(Champion, "Champion of Streetwise Boxing", "Physical DMG +10%", "ATK +5% per attack dealt or taken, up to 5 stacks"),
+        cavern(Guard, "Guard of Wuthering Snow", "DMG taken -8%", "restores HP and Energy each turn at 50% HP or lower"),
+        cavern(Firesmith, "Firesmith of Lava-Forging", "Fire DMG +10%", "Skill DMG +12%, Fire DMG +12% on the next attack after an Ultimate"),
+        cavern(Genius, "Genius of Brilliant Stars", "Quantum DMG +10%", "ignores 10% DEF, 20% against Quantum weak enemies"),
+        cavern(Band, "Band of Sizzling Thunder", "Lightning DMG +10%", "ATK +20% for 1 turn after a Skill"),
+        cavern(Eagle, "Eagle of Twilight Line", "Wind DMG +10%", "action advanced 25% after an Ultimate"),
+        cavern(Thief, "Thief of Shooting Meteor", "Break Effect +16%", "Break Effect +16%, 3 Energy on Weakness Break"),
+        cavern(Wastelander, "Wastelander of Banditry Desert", "Imaginary DMG +10%", "CRIT Rate +10% against debuffed enemies, CRIT DMG +20% against imprisoned ones"),
+        cavern(LongevousDisciple, "Longevous Disciple", "Max HP +12%", "CRIT Rate +8% when hit or losing HP, up to 2 stacks"),
+        cavern(Messenger, "Messenger Traversing Hackerspace", "SPD +6%", "all allies SPD +12% for 1 turn after an Ultimate on an ally"),
+        cavern(GrandDuke, "The Ashblazing Grand Duke", "follow-up attack DMG +20%", "ATK +6% per follow-up attack hit, up to 8 stacks"),
+        cavern(Prisoner, "Prisoner in Deep Confinement", "ATK +12%", "ignores 6% DEF per DoT on the enemy, up to 3"),
+        cavern(PioneerDiver, "Pioneer Diver of Dead Waters", "DMG +12% against debuffed enemies", "CRIT Rate +4%, CRIT DMG against enemies with 2 or 3 debuffs"),
+        cavern(Watchmaker, "Watchmaker, Master of Dream Machinations", "Break Effect +16%", "all allies Break Effect +30% after an Ultimate on an ally"),
+        cavern(IronCavalry, "Iron Cavalry Against the Scourge", "Break Effect +16%", "Break and Super Break DMG ignore DEF at 150% and 250% Break Effect"),
+        cavern(WindSoaring, "The Wind-Soaring Valorous", "ATK +12%", "CRIT Rate +6%, Ultimate DMG +36% after a follow-up attack"),
+        cavern(Sacerdos, "Sacerdos' Relived Ordeal", "SPD +6%", "CRIT DMG +18% to allies targeted by a Skill or Ultimate, up to 2 stacks"),
+        cavern(Scholar, "Scholar Lost in Erudition", "CRIT Rate +8%", "Skill and Ultimate DMG +20%, more Skill DMG after an Ultimate"),
+        cavern(Hero, "Hero of Triumphant Song", "ATK +12%", "SPD +6% with a memosprite, CRIT DMG +30% when it attacks"),
+        cavern(Poet, "Poet of Mourning Collapse", "Quantum DMG +10%", "SPD -8%, CRIT Rate +20% below 110 SPD and +32% below 95 SPD"),
+        cavern(WarriorGoddess, "Warrior Goddess of Sun and Thunder", "SPD +6%", "SPD +6% and all allies CRIT DMG +15% after healing"),
+        cavern(Wavestrider, "Wavestrider Captain", "CRIT DMG +16%", "ATK +48% on an Ultimate after being targeted by allies twice"),
+        cavern(Deliverer, "World-Remaking Deliverer", "CRIT Rate +8%", "Max HP +24% and all allies DMG +15% after a Basic ATK or Skill"),
+        cavern(Recluse, "Self-Enshrouded Recluse", "shields +10%", "shields +12%, shielded allies CRIT DMG +15%"),
+        planar(SpaceSealingStation, "Space Sealing Station", "ATK +12%, another +12% at 120 SPD or more"),
+        planar(FleetOfTheAgeless, "Fleet of the Ageless", "Max HP +12%, all allies ATK +8% at 120 SPD or more"),
+        planar(PanCosmic, "Pan-Cosmic Commercial Enterprise", "Effect Hit Rate +10%, ATK +25% of Effect Hit Rate up to 25%"),
+        planar(Belobog, "Belobog of the Architects", "DEF +15%, another +15% at 50% Effect Hit Rate or more"),
+        planar(CelestialDifferentiator, "Celestial Differentiator", "CRIT DMG +16%, CRIT Rate +60% on the first attack at 120% CRIT DMG or more"),
+        planar(InertSalsotto, "Inert Salsotto", "CRIT Rate +8%, Ultimate and follow-up DMG +15% at 50% CRIT Rate or more"),
+        planar(Talia, "Talia: Kingdom of Banditry", "Break Effect +16%, another +20% at 145 SPD or more"),
+        planar(Vonwacq, "Sprightly Vonwacq", "Energy Regeneration Rate +5%, 40% action advance at battle start at 120 SPD or more"),
+        planar(RutilantArena, "Rutilant Arena", "CRIT Rate +8%, Basic ATK and Skill DMG +20% at 70% CRIT Rate or more"),
+        planar(BrokenKeel, "Broken Keel", "Effect RES +10%, all allies CRIT DMG +10% at 30% Effect RES or more"),
+        planar(Glamoth, "Firmament Frontline: Glamoth", "ATK +12%, DMG +12% at 135 SPD and +18% at 160 SPD"),
+        planar(Penacony, "Penacony, Land of the Dreams", "Energy Regeneration Rate +5%, DMG +10% for allies of the same type"),
+        planar(Sigonia, "Sigonia, the Unclaimed Desolation", "CRIT Rate +4%, CRIT DMG +4% per defeated enemy up to 10 stacks"),
+        planar(Izumo, "Izumo Gensei and Takama Divine Realm", "ATK +12%, CRIT Rate +12% with an ally on the same path"),
+        planar(Duran, "Duran, Dynasty of Running Wolves", "follow-up attack DMG and CRIT DMG up with allies' follow-up attacks"),
+        planar(Kalpagni, "Forge of the Kalpagni Lantern", "SPD +6%, Break Effect +40% after hitting Fire weak enemies"),
+        planar(Lushaka, "Lushaka, the Sunken Seas", "Energy Regeneration Rate +5%, ATK +12% for the first ally"),
+        planar(BananAmusementPark, "The Wondrous BananAmusement Park", "CRIT DMG +16%, another +32% with a summon on the field"),
+        planar(BoneCollection, "Bone Collection's Serene Demesne", "Max HP +12%, memosprite CRIT DMG +28% at 5000 Max HP or more"),
+        planar(GiantTree, "Giant Tree of Rapt Brooding", "SPD +6%, Outgoing Healing up at 135 and 180 SPD"),
+        planar(Arcadia, "Arcadia of Woven Dreams", "DMG up or down with the number of allies on the field"),
+        planar(Revelry, "Revelry by the Sea", "ATK +12%, DoT +12% at 2400 ATK and +24% at 3600 ATK"),
+    ]
+};
+
+/// The two sets every domain (cavern of corrosion or simulated universe) drops
+const DOMAINS: &[[RelicSet; 2]] = {
+    use RelicSet::*;
+    &[
+        [Passerby, Musketeer],
+        [Knight, Hunter],
+        [Champion, Guard],
+        [Firesmith, Genius],
+        [Band, Eagle],
+        [Thief, Wastelander],
+        [LongevousDisciple, Messenger],
+        [GrandDuke, Prisoner],
+        [PioneerDiver, Watchmaker],
+        [IronCavalry, WindSoaring],
+        [Sacerdos, Scholar],
+        [Hero, Poet],
+        [WarriorGoddess, Wavestrider],
+        [Deliverer, Recluse],
+        [SpaceSealingStation, FleetOfTheAgeless],
+        [PanCosmic, Belobog],
+        [CelestialDifferentiator, InertSalsotto],
+        [Talia, Vonwacq],
+        [RutilantArena, BrokenKeel],
+        [Glamoth, Penacony],
+        [Sigonia, Izumo],
+        [Duran, Kalpagni],
+        [Lushaka, BananAmusementPark],
+        [BoneCollection, GiantTree],
+        [Arcadia, Revelry],
+    ]
+};
+
+impl RelicSet {
+    pub fn all() -> impl Iterator<Item=RelicSet> {
+        SETS.iter().map(|info| info.set)
+    }
+
+    pub fn info(&self) -> &'static SetInfo {
+        SETS.iter()
+            .find(|info| info.set == *self)
+            .expect("every set is in the catalog")
+    }
+
+    pub fn name(&self) -> &'static str {
+        self.info().name
+    }
+
+    pub fn kind(&self) -> SetKind {
+        self.info().kind
+    }
+
+    /// Slots pieces of this set drop in
+    pub fn slots(&self) -> &'static [RelicSlot] {
+        match self.kind() {
+            SetKind::Cavern => &[RelicSlot::Head, RelicSlot::Hands, RelicSlot::Body, RelicSlot::Feet],
+            SetKind::Planar => &[RelicSlot::Orb, RelicSlot::Rope],
+        }
+    }
+
+    /// Domain this set drops from
+    pub fn domain(&self) -> Domain {
+        Domain::all()
+            .find(|domain| domain.sets.contains(self))
+            .expect("every set drops from a domain")
+    }
+}
+
+/// A farmable domain and the pair of sets it drops, each with probability `p_set`
+#[derive(PartialEq, Eq, Hash, Copy, Clone, Debug, Serialize)]
+pub struct Domain {
+    pub sets: [RelicSet; 2],
+}
+
+impl Domain {
+    pub fn all() -> impl Iterator<Item=Domain> {
+        DOMAINS.iter().map(|&sets| Domain { sets })
+    }
+
+    /// Slots the domain drops
+    pub fn slots(&self) -> &'static [RelicSlot] {
+        self.sets[0].slots()
+    }
+
+    /// Probability of a drop being one of `accepted`
+    pub fn p_accepted(&self, accepted: &[RelicSet]) -> f64 {
+        let p_set = GameTables::with(|tables| tables.p_set());
+        self.sets.iter().filter(|set| accepted.contains(set)).count() as f64 * p_set
+    }
+
+    /// Domain where the most drops are one of `accepted`, none if nothing is accepted
+    pub fn best_for(accepted: &[RelicSet]) -> Option<Domain> {
+        accepted.iter()
+            .map(RelicSet::domain)
+            .max_by(|a, b| a.p_accepted(accepted).total_cmp(&b.p_accepted(accepted)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use itertools::Itertools;
+
+    #[test]
+    fn domains() {
+        // every set drops from exactly one domain
+        let sets = Domain::all().flat_map(|domain| domain.sets).collect::<Vec<_>>();
+        assert_eq!(sets.len(), sets.iter().unique().count());
+        assert!(sets.iter().all(|set| set.domain().sets.contains(set)));
+
+        assert_eq!([RelicSet::Firesmith, RelicSet::Genius], RelicSet::Genius.domain().sets);
+        assert_eq!(1.0, RelicSet::Genius.domain().p_accepted(&[RelicSet::Genius, RelicSet::Firesmith, RelicSet::Knight]));
+        assert_eq!(None, Domain::best_for(&[]));
+        assert_eq!(Some(RelicSet::Knight.domain()), Domain::best_for(&[RelicSet::Genius, RelicSet::Knight, RelicSet::Hunter]));
+        assert_eq!("\"Watchmaker, Master of Dream Machinations\"", serde_json::to_string(&RelicSet::Watchmaker).unwrap());
+
+        // the catalog covers every set once and agrees with serde
+        assert_eq!(sets.len(), RelicSet::all().count());
+        for set in RelicSet::all() {
+            assert_eq!(set, set.info().set);
+            assert_eq!(serde_json::to_string(&set).unwrap(), format!("\"{}\"", set.name()));
+            assert_eq!(Ok(set), set.name().parse());
+            // a domain drops either relics or ornaments
+            assert!(set.domain().sets.iter().all(|s| s.kind() == set.kind()));
+            assert_eq!(set.kind() == SetKind::Cavern, set.info().four_piece.is_some());
+        }
+        assert_eq!(Ok(RelicSet::Genius), "genius of brilliant stars".parse());
+        assert_eq!(Ok(RelicSet::Genius), "Genius".parse());
+        assert_eq!(&[RelicSlot::Orb, RelicSlot::Rope], RelicSet::Glamoth.slots());
+    }
+}
@@ -0,0 +1,369 @@
+use crate::{DropModel, InvalidRelicError, LevelingCosts, Relic, RelicSlot, RelicStat, RollTier, SubstatRoll};
+
+/// Mean of a simulated quantity, with its standard error
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Estimate {
+    pub mean: f64,
+    pub std_error: f64,
+    pub samples: usize,
+}
+
+impl Estimate {
+    fn from_samples(samples: impl IntoIterator<Item=f64>) -> Self {
+        let samples = samples.into_iter().collect::<Vec<_>>();
+        let n = samples.len() as f64;
+        let mean = samples.iter().sum::<f64>() / n;
+        let variance = samples.iter().map(|x| (x - mean).powi(2)).sum::<f64>() / (n - 1.0).max(1.0);
+        Self { mean, std_error: (variance / n).sqrt(), samples: samples.len() }
+    }
+
+    /// Normal 95% confidence interval
+    pub fn confidence_interval(&self) -> (f64, f64) {
+        (self.mean - 1.96 * self.std_error, self.mean + 1.96 * self.std_error)
+    }
+}
+
+/// A probability computed by the library next to the same probability simulated
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct CrossCheck {
+    pub analytic: f64,
+    pub simulated: Estimate,
+}
+
+impl CrossCheck {
+    /// Distance between the two, in standard errors of the simulation
+    pub fn z_score(&self) -> f64 {
+        let difference = self.simulated.mean - self.analytic;
+        if self.simulated.std_error > 0.0 {
+            difference / self.simulated.std_error
+        } else if difference.abs() < 1e-12 {
+            0.0
+        } else {
+            f64::INFINITY
+        }
+    }
+
+    /// Whether the analytic result is within `z` standard errors of the simulation
+    pub fn agrees(&self, z: f64) -> bool {
+        self.z_score().abs() <= z
+    }
+}
+
+/// How long farming a relic took in one simulation
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct FarmingRun {
+    pub runs: usize,
+    /// TBP spent on domain runs
+    pub tbp: f64,
+    /// Net TBP spent leveling relics, fed EXP given back
+    pub leveling_tbp: f64,
+    /// False when the run limit was hit first
+    pub success: bool,
+}
+
+/// Many [`FarmingRun`]s summed up
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct FarmingSimulation {
+    pub runs: Estimate,
+    pub tbp: Estimate,
+    pub leveling_tbp: Estimate,
+    /// Domain runs and leveling together
+    pub total_tbp: Estimate,
+    pub p_success: Estimate,
+}
+
+/// Seeded Monte Carlo simulation of domain drops and relic upgrades, following the game's
+/// rules rather than the library's formulas so the two can be checked against each other
+///
+/// Simulated relics list their initial lines first, then one roll per upgrade in the
+/// order they happened. Tiers are not canonical like in [`Relic::filtered_p_sub`], so
+/// compare with filters on stat totals.
+#[derive(Clone, Debug)]
+pub struct Simulator {
+    rng: Rng,
+    drop_model: DropModel,
+    costs: LevelingCosts,
+    max_runs: usize,
+}
+
+impl Simulator {
+    pub fn new(seed: u64) -> Self {
+        Self {
+            rng: Rng(seed),
+            drop_model: DropModel::default(),
+            costs: LevelingCosts::free(),
+            max_runs: 100_000,
+        }
+    }
+    pub fn drop_model(mut self, drop_model: DropModel) -> Self {
+        self.drop_model = drop_model;
+        self
+    }
+    /// What leveling costs, free unless set
+    pub fn leveling_costs(mut self, costs: LevelingCosts) -> Self {
+        self.costs = costs;
+        self
+    }
+    /// Runs after which a farming simulation gives up
+    pub fn max_runs(mut self, runs: usize) -> Self {
+        self.max_runs = runs;
+        self
+    }
+
+    /// A +0 relic from the domain that drops `target`, `None` when it is from the other set
+    pub fn roll_drop(&mut self, target: &Relic) -> Option<Relic> {
+        use RelicSlot::*;
+
+        if self.rng.next_f64() >= target.p_main_set() {
+            return None;
+        }
+
+        let slots: &[RelicSlot] = match target.slot {
+            Head | Hands | Body | Feet => &[Head, Hands, Body, Feet],
+            Orb | Rope => &[Orb, Rope],
+        };
+        let slots = slots.iter()
+            .map(|slot| (*slot, Relic::new(target.rarity, *slot, target.main).p_main_slot()))
+            .collect::<Vec<_>>();
+        let slot = self.rng.weighted(&slots);
+
+        let mains = slot.mains()
+            .map(|main| (main, Relic::new(target.rarity, slot, main).p_main_stat_unchecked()))
+            .collect::<Vec<_>>();
+        let main = self.rng.weighted(&mains);
+
+        let mut relic = Relic::new(target.rarity, slot, main);
+        self.roll_initial_subs(&mut relic);
+        Some(relic)
+    }
+
+    /// Replaces the substats of `relic` with new initial lines and sets it back to +0
+    pub fn roll_initial_subs(&mut self, relic: &mut Relic) {
+        let max_initial = relic.rarity.saturating_sub(1);
+        let initial = if self.rng.next_f64() < relic.p_initial_lines(max_initial) {
+            max_initial
+        } else {
+            max_initial.saturating_sub(1)
+        };
+
+        relic.level = 0;
+        relic.subs.clear();
+        for _ in 0..initial {
+            self.add_line(relic);
+        }
+    }
+
+    /// Levels `relic` up by 3: a new line while it has less than four, otherwise one more
+    /// roll on one of its four lines
+    pub fn upgrade(&mut self, relic: &mut Relic) {
+        let lines = relic.subs.iter().take(4).map(|r| r.stat).collect::<Vec<_>>();
+        if lines.len() < 4 {
+            self.add_line(relic);
+        } else {
+            let stat = lines[self.rng.below(4)];
+            let tier = self.roll_tier();
+            relic.subs.push(SubstatRoll::new(stat, tier));
+        }
+        relic.level += 3;
+    }
+
+    /// Upgrades `relic` until it is fully leveled
+    pub fn level_up(&mut self, relic: &mut Relic) {
+        while relic.level < relic.max_level() {
+            self.upgrade(relic);
+        }
+    }
+
+    /// Fraction of fully leveled relics of the rarity, slot and main stat of `relic` that
+    /// pass `filter`
+    pub fn estimate_p_sub(&mut self, relic: &Relic, samples: usize, mut filter: impl FnMut(&Relic) -> bool) -> Estimate {
+        let mut relic = relic.clone();
+        Estimate::from_samples((0..samples).map(|_| {
+            self.roll_initial_subs(&mut relic);
+            self.level_up(&mut relic);
+            if filter(&relic) { 1.0 } else { 0.0 }
+        }))
+    }
+
+    /// [`Relic::filtered_p_sub`] next to [`Simulator::estimate_p_sub`]
+    pub fn check_p_sub(&mut self, relic: &Relic, samples: usize, mut filter: impl FnMut(&Relic) -> bool) -> Result<CrossCheck, InvalidRelicError> {
+        Ok(CrossCheck {
+            analytic: relic.filtered_p_sub(&mut filter)?,
+            simulated: self.estimate_p_sub(relic, samples, &mut filter),
+        })
+    }
+
+    /// Farms the domain of `target` until a relic of its slot and main stat is a `success`
+    ///
+    /// Matching relics are upgraded as long as `keep_leveling` says so, and only count as a
+    /// success when fully leveled. Everything else is fed.
+    pub fn farm(
+        &mut self,
+        target: &Relic,
+        mut keep_leveling: impl FnMut(&Relic) -> bool,
+        mut success: impl FnMut(&Relic) -> bool,
+    ) -> FarmingRun {
+        let mut farming = FarmingRun { runs: 0, tbp: 0.0, leveling_tbp: 0.0, success: false };
+
+        while !farming.success && farming.runs < self.max_runs {
+            farming.runs += 1;
+            farming.tbp += self.drop_model.run_tbp();
+
+            let drops = self.drop_model.sample_drops(self.rng.next_f64());
+            for _ in 0..drops {
+                let Some(mut relic) = self.roll_drop(target) else { continue };
+                if relic.slot != target.slot || relic.main != target.main {
+                    continue;
+                }
+
+                while relic.level < relic.max_level() && keep_leveling(&relic) {
+                    self.upgrade(&mut relic);
+                }
+
+                let upgrades = relic.level / 3;
+                farming.leveling_tbp += self.costs.leveling_tbp(relic.rarity, upgrades);
+                if relic.level == relic.max_level() && success(&relic) {
+                    farming.success = true;
+                    break;
+                }
+                farming.leveling_tbp -= self.costs.feeding_tbp(relic.rarity, upgrades);
+            }
+        }
+        farming
+    }
+
+    /// [`Simulator::farm`] repeated `trials` times
+    pub fn estimate_farming(
+        &mut self,
+        target: &Relic,
+        trials: usize,
+        mut keep_leveling: impl FnMut(&Relic) -> bool,
+        mut success: impl FnMut(&Relic) -> bool,
+    ) -> FarmingSimulation {
+        let runs = (0..trials)
+            .map(|_| self.farm(target, &mut keep_leveling, &mut success))
+            .collect::<Vec<_>>();
+
+        FarmingSimulation {
+            runs: Estimate::from_samples(runs.iter().map(|r| r.runs as f64)),
+            tbp: Estimate::from_samples(runs.iter().map(|r| r.tbp)),
+            leveling_tbp: Estimate::from_samples(runs.iter().map(|r| r.leveling_tbp)),
+            total_tbp: Estimate::from_samples(runs.iter().map(|r| r.tbp + r.leveling_tbp)),
+            p_success: Estimate::from_samples(runs.iter().map(|r| if r.success { 1.0 } else { 0.0 })),
+        }
+    }
+
+    fn add_line(&mut self, relic: &mut Relic) {
+        let pool = RelicStat::possible_sub_stats()
+            .filter(|s| *s != relic.main && relic.sub_rolls(*s) == 0)
+            .map(|s| (s, s.substat_probability_weight() as f64))
+            .collect::<Vec<_>>();
+        let stat = self.rng.weighted(&pool);
+        let tier = self.roll_tier();
+        relic.subs.push(SubstatRoll::new(stat, tier));
+    }
+
+    fn roll_tier(&mut self) -> RollTier {
+        RollTier::all().nth(self.rng.below(3)).unwrap()
+    }
+}
+
+// SplitMix64, small and good enough for simulations
+#[derive(Clone, Debug)]
+struct Rng(u64);
+
+impl Rng {
+    fn next_u64(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9e3779b97f4a7c15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d049bb133111eb);
+        z ^ (z >> 31)
+    }
+
+    // uniform in [0, 1)
+    fn next_f64(&mut self) -> f64 {
+        (self.next_u64() >> 11) as f64 / (1u64 << 53) as f64
+    }
+
+    fn below(&mut self, n: usize) -> usize {
+        ((self.next_f64() * n as f64) as usize).min(n - 1)
+    }
+
+    fn weighted<T: Copy>(&mut self, items: &[(T, f64)]) -> T {
+        let total = items.iter().map(|(_, w)| w).sum::<f64>();
+        let mut x = self.next_f64() * total;
+        for (item, weight) in items {
+            if x < *weight {
+                return *item;
+            }
+            x -= weight;
+        }
+        items[items.len() - 1].0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{FarmingEstimate, GameTables, LinearScore};
+    use RelicStat::*;
+
+    #[test]
+    fn matches_analytic() {
+        // reproducible
+        let relic = Relic::new(4, RelicSlot::Body, CritRate);
+        let mut a = Simulator::new(7);
+        let mut b = Simulator::new(7);
+        assert_eq!(
+            a.estimate_p_sub(&relic, 100, |r| r.sub_rolls(CritDmg) > 1),
+            b.estimate_p_sub(&relic, 100, |r| r.sub_rolls(CritDmg) > 1),
+        );
+
+        let mut simulator = Simulator::new(1);
+        let relic = Relic::new(3, RelicSlot::Body, CritRate);
+        for filter in [
+            (|r: &Relic| r.sub_rolls(CritDmg) >= 2) as fn(&Relic) -> bool,
+            |r: &Relic| r.sub_value(Spd) > 1.5,
+        ] {
+            let check = simulator.check_p_sub(&relic, 20_000, filter).unwrap();
+            assert!(check.agrees(4.0), "{check:?}");
+        }
+
+        // several upgrades on the same line is where the upgrade model matters
+        let relic = Relic::new(5, RelicSlot::Feet, Spd);
+        let crit = LinearScore::roll_weights([(CritRate, 1.0), (CritDmg, 1.0)].into());
+        let check = CrossCheck {
+            analytic: relic.score_distribution(&crit).unwrap().p_at_least(5.0),
+            simulated: simulator.estimate_p_sub(&relic, 20_000, |r| crit.score(r) >= 5.0),
+        };
+        assert!(check.agrees(4.0), "{check:?}");
+
+        // slots drop at the rates of the game tables, here heads 70% of the time
+        let mut json = serde_json::from_str::<serde_json::Value>(&GameTables::embedded().to_json()).unwrap();
+        json["p_slot"] = serde_json::json!({ "Head": 0.7, "Hands": 0.1, "Body": 0.1, "Feet": 0.1, "PlanarSphere": 0.5, "LinkRope": 0.5 });
+        let heavy_heads = GameTables::from_json(&json.to_string()).unwrap();
+        let target = Relic::new(5, RelicSlot::Body, CritRate);
+        let check = heavy_heads.scope(|| CrossCheck {
+            analytic: target.p_main_set() * Relic::new(5, RelicSlot::Head, Hp).p_main_slot(),
+            simulated: Estimate::from_samples((0..20_000).map(|_| match simulator.roll_drop(&target) {
+                Some(relic) if relic.slot == RelicSlot::Head => 1.0,
+                _ => 0.0,
+            })),
+        });
+        assert!(check.agrees(4.0), "{check:?}");
+
+        // farming until the first success is geometric in runs
+        let target = Relic::new(3, RelicSlot::Head, Hp);
+        let success = |r: &Relic| r.sub_rolls(CritDmg) > 0;
+        let free = LevelingCosts::free();
+        let mut simulator = Simulator::new(2).leveling_costs(free);
+        let farming = simulator.estimate_farming(&target, 2_000, |_| true, success);
+
+        let expected = FarmingEstimate::new(target.p_main().unwrap() * target.filtered_p_sub(success).unwrap(), DropModel::default());
+        let check = CrossCheck { analytic: expected.expected_tbp(), simulated: farming.tbp };
+        assert!(check.agrees(4.0), "{check:?}");
+        assert_eq!(0.0, farming.leveling_tbp.mean);
+        assert_eq!(1.0, farming.p_success.mean);
+    }
+}
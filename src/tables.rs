@@ -0,0 +1,249 @@
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::OnceLock;
+
+use serde::{Deserialize, Serialize};
+
+use crate::{RelicSlot, RelicStat};
+
+const EMBEDDED: &str = include_str!("../data/game_tables.json");
+const VERSION: u32 = 1;
+
+thread_local! {
+    static SCOPED: RefCell<Option<GameTables>> = const { RefCell::new(None) };
+}
+
+/// Drop rates and substat values of the game, loaded from `data/game_tables.json`
+///
+/// Every calculation uses the embedded tables unless run inside [`GameTables::scope`], so
+/// alternative tables can be compared against them.
+#[derive(Clone, Debug, PartialEq)]
+pub struct GameTables {
+    file: TablesFile,
+    // substat weights by stat index, they are looked up a lot
+    substat_weight: [u8; 21],
+}
+
+// layout of the data file
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+struct TablesFile {
+    version: u32,
+    #[serde(default)]
+    source: String,
+    p_set: f64,
+    p_slot: HashMap<RelicSlot, f64>,
+    p_main_stat: HashMap<RelicSlot, HashMap<RelicStat, f64>>,
+    // probability of a relic starting with the most initial lines its rarity allows
+    p_max_initial_lines: f64,
+    substat_weight: HashMap<RelicStat, u8>,
+    sub_roll_low_and_step: HashMap<usize, HashMap<RelicStat, (f64, f64)>>,
+    // main stat value at +0 and what each level adds, for every rarity
+    #[serde(default)]
+    main_base_and_step: HashMap<usize, HashMap<RelicStat, (f64, f64)>>,
+}
+
+impl GameTables {
+    /// Tables shipped with the crate
+    pub fn embedded() -> &'static GameTables {
+        static TABLES: OnceLock<GameTables> = OnceLock::new();
+        TABLES.get_or_init(|| GameTables::from_json(EMBEDDED).expect("embedded game tables are valid"))
+    }
+
+    pub fn from_json(json: &str) -> Result<Self, ParseGameTablesError> {
+        let file = serde_json::from_str::<TablesFile>(json)
+            .map_err(|e| ParseGameTablesError(e.to_string()))?;
+        file.validate()?;
+
+        let mut substat_weight = [0; 21];
+        for (stat, weight) in &file.substat_weight {
+            substat_weight[*stat as usize] = *weight;
+        }
+        Ok(Self { file, substat_weight })
+    }
+
+    pub fn to_json(&self) -> String {
+        serde_json::to_string_pretty(&self.file).expect("game tables serialize")
+    }
+
+    /// Runs `f` with these tables in place of the embedded ones, on this thread
+    pub fn scope<T>(&self, f: impl FnOnce() -> T) -> T {
+        // puts the previous tables back even if `f` panics
+        struct Restore(Option<GameTables>);
+        impl Drop for Restore {
+            fn drop(&mut self) {
+                SCOPED.with(|scoped| *scoped.borrow_mut() = self.0.take());
+            }
+        }
+
+        let _restore = Restore(SCOPED.with(|scoped| scoped.borrow_mut().replace(self.clone())));
+        f()
+    }
+
+    /// Calls `f` with the tables in use on this thread
+    pub(crate) fn with<T>(f: impl FnOnce(&GameTables) -> T) -> T {
+        SCOPED.with(|scoped| match scoped.borrow().as_ref() {
+            Some(tables) => f(tables),
+            None => f(GameTables::embedded()),
+        })
+    }
+
+    /// Where the numbers come from
+    pub fn source(&self) -> &str {
+        &self.file.source
+    }
+
+    pub fn p_set(&self) -> f64 {
+        self.file.p_set
+    }
+
+    pub fn p_slot(&self, slot: RelicSlot) -> f64 {
+        self.file.p_slot.get(&slot).copied().unwrap_or(0.0)
+    }
+
+    pub fn p_main_stat(&self, slot: RelicSlot, main: RelicStat) -> f64 {
+        self.file.p_main_stat.get(&slot)
+            .and_then(|mains| mains.get(&main))
+            .copied()
+            .unwrap_or(0.0)
+    }
+
+    pub fn p_max_initial_lines(&self) -> f64 {
+        self.file.p_max_initial_lines
+    }
+
+    pub fn substat_weight(&self, stat: RelicStat) -> u8 {
+        self.substat_weight[stat as usize]
+    }
+
+    /// Sum of the weights of every substat
+    pub fn substat_weight_total(&self) -> u32 {
+        self.substat_weight.iter().map(|w| *w as u32).sum()
+    }
+
+    /// Value of a low roll and the difference between tiers
+    pub fn sub_roll_low_and_step(&self, stat: RelicStat, rarity: usize) -> (f64, f64) {
+        self.file.sub_roll_low_and_step.get(&rarity)
+            .and_then(|rolls| rolls.get(&stat))
+            .copied()
+            .unwrap_or((0.0, 0.0))
+    }
+
+    /// Value of a main stat at +0 and what each level adds
+    pub fn main_base_and_step(&self, stat: RelicStat, rarity: usize) -> (f64, f64) {
+        self.file.main_base_and_step.get(&rarity)
+            .and_then(|mains| mains.get(&stat))
+            .copied()
+            .unwrap_or((0.0, 0.0))
+    }
+}
+
+impl TablesFile {
+    fn validate(&self) -> Result<(), ParseGameTablesError> {
+        let error = |message: String| Err(ParseGameTablesError(message));
+        let is_probability = |p: f64| (0.0..=1.0).contains(&p);
+
+        if self.version != VERSION {
+            return error(format!("unsupported version {}, expected {VERSION}", self.version));
+        }
+        if !is_probability(self.p_set) || !is_probability(self.p_max_initial_lines) {
+            return error("probabilities must be between 0 and 1".to_string());
+        }
+        for (slot, mains) in &self.p_main_stat {
+            let total = mains.values().sum::<f64>();
+            if (total - 1.0).abs() > 1e-6 {
+                return error(format!("main stat probabilities of {slot:?} add up to {total}, not 1"));
+            }
+        }
+        // a relic that can drop needs a main value, or it would silently count as 0
+        for rarity in 2..=5 {
+            let values = self.main_base_and_step.get(&rarity);
+            for (slot, mains) in &self.p_main_stat {
+                let missing = mains.iter().find(|(main, p)| **p > 0.0 && !values.is_some_and(|v| v.contains_key(main)));
+                if let Some((main, _)) = missing {
+                    return error(format!("no main stat value for {rarity}* {slot:?} {main:?}"));
+                }
+            }
+        }
+        // same for the substats, every one of them can roll at every rarity
+        for rarity in 2..=5 {
+            let rolls = self.sub_roll_low_and_step.get(&rarity);
+            if let Some(sub) = RelicStat::possible_sub_stats().find(|sub| !rolls.is_some_and(|r| r.contains_key(sub))) {
+                return error(format!("no substat roll for {rarity}* {sub:?}"));
+            }
+        }
+        if let Some(stat) = self.substat_weight.keys().find(|s| !RelicStat::possible_sub_stats().any(|sub| sub == **s)) {
+            return error(format!("{stat:?} can't be a substat"));
+        }
+        Ok(())
+    }
+}
+
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseGameTablesError(String);
+
+impl fmt::Display for ParseGameTablesError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "invalid game tables: {}", self.0)
+    }
+}
+
+impl std::error::Error for ParseGameTablesError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::assert_float_eq;
+    use crate::{Relic, RollTier};
+    use RelicStat::*;
+
+    #[test]
+    fn game_tables() {
+        let tables = GameTables::embedded();
+        assert_eq!(100, tables.substat_weight_total());
+        assert_eq!(4, tables.substat_weight(Spd));
+        assert_eq!(0, tables.substat_weight(IceDmgBoost));
+        assert_eq!((2.0, 0.3), tables.sub_roll_low_and_step(Spd, 5));
+        assert_eq!((1.2, 0.1), tables.sub_roll_low_and_step(Spd, 3));
+        assert_float_eq(0.4 * CritDmg.sub_roll_value(5, RollTier::Low), CritDmg.sub_roll_value(2, RollTier::Low));
+        assert_eq!((4.032, 1.4), tables.main_base_and_step(Spd, 5));
+        assert_float_eq(25.032, Spd.main_value(5, 15));
+        assert_float_eq(32.4, CritRate.main_value(5, 15));
+        assert_float_eq(705.6, Hp.main_value(5, 15));
+        assert_float_eq(11.4192, Spd.main_value(3, 9));
+        assert_float_eq(8.57088, AtkPercent.main_value(2, 6));
+        assert_eq!(1.0, tables.p_main_stat(RelicSlot::Head, Hp));
+        assert_eq!(0.0, tables.p_main_stat(RelicSlot::Head, Atk));
+        assert_eq!(tables, &GameTables::from_json(&tables.to_json()).unwrap());
+
+        // alternative tables only apply inside their scope
+        let relic = Relic::new(5, RelicSlot::Feet, Spd);
+        let mut json = serde_json::from_str::<serde_json::Value>(EMBEDDED).unwrap();
+        json["p_main_stat"]["Feet"] = serde_json::json!({ "SPD": 0.4, "ATK%": 0.6 });
+        json["sub_roll_low_and_step"]["5"]["SPD"] = serde_json::json!([2.0, 0.4]);
+        let alternative = GameTables::from_json(&json.to_string()).unwrap();
+
+        assert_eq!(0.1, relic.p_main_stat().unwrap());
+        alternative.scope(|| {
+            assert_eq!(0.4, relic.p_main_stat().unwrap());
+            assert_float_eq(2.8, Spd.sub_roll_value(5, RollTier::High));
+        });
+        assert_eq!(0.1, relic.p_main_stat().unwrap());
+        assert_float_eq(2.6, Spd.sub_roll_value(5, RollTier::High));
+
+        json["p_main_stat"]["Feet"] = serde_json::json!({ "SPD": 0.4 });
+        assert!(GameTables::from_json(&json.to_string()).is_err());
+        assert!(GameTables::from_json("{}").is_err());
+
+        // every main stat that can drop needs a value at every rarity
+        json["p_main_stat"]["Feet"] = serde_json::json!({ "SPD": 0.4, "ATK%": 0.6 });
+        json["main_base_and_step"]["2"].as_object_mut().unwrap().remove("SPD");
+        let error = GameTables::from_json(&json.to_string()).unwrap_err();
+        assert!(error.to_string().contains("2* Feet Spd"), "{error}");
+        json["main_base_and_step"]["2"]["SPD"] = serde_json::json!([1.6128, 1.0]);
+        json["sub_roll_low_and_step"]["3"].as_object_mut().unwrap().remove("CRIT DMG");
+        let error = GameTables::from_json(&json.to_string()).unwrap_err();
+        assert!(error.to_string().contains("3* CritDmg"), "{error}");
+    }
+}
@@ -0,0 +1,112 @@
+use std::collections::HashMap;
+
+use serde::{Deserialize, Serialize};
+
+use crate::{Relic, RelicStat};
+
+/// Totals of stats a build needs across its relics, e.g. 134 SPD or 70% CRIT Rate
+///
+/// Serializes as e.g. `{"min": {"SPD": 134.0}, "base": {"SPD": 101.0, "CRIT Rate": 5.0}}`. Main
+/// stats and substats of a stat are added up as shown in game, flat and percentage stats aren't
+/// combined.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct StatTargets {
+    min: HashMap<RelicStat, f64>,
+    base: HashMap<RelicStat, f64>,
+}
+
+impl StatTargets {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// `stat` has to add up to at least `total`
+    pub fn min(mut self, stat: RelicStat, total: f64) -> Self {
+        self.min.insert(stat, total);
+        self
+    }
+
+    /// What the character, light cone and traces give without relics
+    pub fn base(mut self, stat: RelicStat, value: f64) -> Self {
+        self.base.insert(stat, value);
+        self
+    }
+
+    pub fn total<'a>(&self, stat: RelicStat, relics: impl IntoIterator<Item=&'a Relic>) -> f64 {
+        self.base.get(&stat).copied().unwrap_or(0.0) + relics.into_iter().map(|r| r.stat_value(stat)).sum::<f64>()
+    }
+
+    /// How much of each stat is still missing with `relics`, empty once every target is met
+    pub fn missing<'a>(&self, relics: impl IntoIterator<Item=&'a Relic>) -> Vec<(RelicStat, f64)> {
+        let relics = relics.into_iter().collect::<Vec<_>>();
+        let mut missing = self.min.iter()
+            .map(|(stat, min)| (*stat, min - self.total(*stat, relics.iter().copied())))
+            // keep float noise from missing a target that was reached exactly
+            .filter(|(_, missing)| *missing > 1e-9)
+            .collect::<Vec<_>>();
+        missing.sort_by_key(|(stat, _)| *stat);
+        missing
+    }
+
+    pub fn is_met<'a>(&self, relics: impl IntoIterator<Item=&'a Relic>) -> bool {
+        self.missing(relics).is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::assert_float_eq;
+    use crate::{ConditionalRelicProbabilityCalculator, LinearScore, RelicSlot, RollTier, SubstatRoll};
+    use RelicStat::*;
+
+    #[test]
+    fn stat_targets() {
+        let high = |stat| SubstatRoll::new(stat, RollTier::High);
+        let mut feet = Relic::new(5, RelicSlot::Feet, Spd).copy_with_new_subs([high(CritRate)]);
+        feet.level = 15;
+        let head = Relic::new(5, RelicSlot::Head, Hp).copy_with_new_subs([high(Spd), high(Spd)]);
+
+        // main stats count at their level
+        let targets = StatTargets::new().base(Spd, 100.0).min(Spd, 134.0).base(CritRate, 5.0).min(CritRate, 5.0);
+        assert_float_eq(100.0 + 25.032 + 5.2, targets.total(Spd, [&feet, &head]));
+        assert_float_eq(5.0 + 3.24, targets.total(CritRate, [&feet, &head]));
+        assert_eq!(vec![Spd], targets.missing([&feet, &head]).into_iter().map(|(stat, _)| stat).collect::<Vec<_>>());
+        assert!(targets.clone().min(Spd, 130.232).is_met([&feet, &head]));
+        feet.level = 0;
+        assert_float_eq(100.0 + 4.032 + 5.2, targets.total(Spd, [&feet, &head]));
+        feet.level = 15;
+
+        // a single stat agrees with the distribution of its value
+        let calculator = ConditionalRelicProbabilityCalculator::new().consider_slot();
+        let value = |stat| LinearScore::value_weights([(stat, 1.0)].into());
+        let hands = Relic::new(4, RelicSlot::Hands, Atk);
+        let spd = StatTargets::new().base(Spd, 100.0).min(Spd, 129.0);
+        let p_spd = calculator.calculate_for_targets(&hands, &spd, [&feet]).unwrap();
+        assert!(p_spd > 0.0);
+        assert_float_eq(calculator.calculate_for_score(&hands, &value(Spd), 29.0 - 25.032).unwrap(), p_spd);
+
+        // several stats have to be reached by the same drop, checked against every outcome
+        let both = spd.clone().base(CritRate, 5.0).min(CritRate, 10.0);
+        let p_both = calculator.calculate_for_targets(&hands, &both, [&feet]).unwrap();
+        let brute_force = calculator
+            .calculate_for_relic(&hands, |r| r.stat_value(Spd) >= 29.0 - 25.032 && r.stat_value(CritRate) >= 10.0 - 5.0 - 3.24)
+            .unwrap();
+        assert_float_eq(brute_force, p_both);
+        assert!(0.0 < p_both && p_both < p_spd);
+
+        // a crit rate body is enough with its main stat alone, a crit dmg body needs the rolls
+        let bodies = calculator.clone().consider_main().accept_mains([CritRate, CritDmg]);
+        let body = |main| Relic::new(4, RelicSlot::Body, main);
+        let crit = StatTargets::new().base(CritRate, 5.0).min(CritRate, 20.0);
+        let p_crit_dmg = body(CritDmg).score_distribution(&value(CritRate)).unwrap().p_at_least(20.0 - 5.0 - 3.24);
+        assert_float_eq(
+            body(CritDmg).p_main_slot() * (body(CritRate).p_main_stat().unwrap() + body(CritDmg).p_main_stat().unwrap() * p_crit_dmg),
+            bodies.calculate_for_targets(&body(CritDmg), &crit, [&feet]).unwrap(),
+        );
+
+        // nothing missing takes any drop
+        assert_float_eq(hands.p_main_slot(), calculator.calculate_for_targets(&hands, &StatTargets::new(), []).unwrap());
+    }
+}
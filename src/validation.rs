@@ -0,0 +1,167 @@
+use std::fmt;
+
+use itertools::Itertools;
+
+use crate::{GameTables, Relic, RelicSet, RelicSlot, RelicStat, SubstatRoll};
+
+/// Why a relic can't exist in the game
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum InvalidRelicError {
+    /// Relics are 2* to 5*
+    Rarity(usize),
+    /// The main stat never drops in this slot, e.g. anything but HP on a head
+    MainStat { slot: RelicSlot, main: RelicStat },
+    /// Ornament sets only drop as spheres and ropes, relic sets never do
+    SetSlot { set: RelicSet, slot: RelicSlot },
+    Level { level: usize, max: usize },
+    /// A main stat only stat in the substats
+    NotASubstat(RelicStat),
+    SubstatIsMain(RelicStat),
+    /// Wrong number of distinct substats for the number of rolls
+    Lines { lines: usize, expected: usize },
+    /// More or fewer rolls than the rarity and level allow
+    Rolls { rolls: usize, level: usize, min: usize, max: usize },
+}
+
+impl fmt::Display for InvalidRelicError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Rarity(rarity) => write!(f, "rarity {rarity} is not between 2 and 5"),
+            Self::MainStat { slot, main } => write!(f, "{main} can't be the main stat of {slot}"),
+            Self::SetSlot { set, slot } => write!(f, "{set} doesn't drop as {slot}"),
+            Self::Level { level, max } => write!(f, "level +{level} is above the max of +{max}"),
+            Self::NotASubstat(stat) => write!(f, "{stat} can't be a substat"),
+            Self::SubstatIsMain(stat) => write!(f, "{stat} is both the main stat and a substat"),
+            Self::Lines { lines, expected } => write!(f, "{lines} distinct substats, expected {expected}"),
+            Self::Rolls { rolls, level, min, max } => {
+                write!(f, "{rolls} substat rolls at +{level}, expected {min} to {max}")
+            }
+        }
+    }
+}
+
+impl std::error::Error for InvalidRelicError {}
+
+impl Relic {
+    /// Same as [`Relic::new`] with a level and substats, checked with [`Relic::validate`]
+    pub fn try_new(
+        rarity: usize,
+        slot: RelicSlot,
+        main: RelicStat,
+        level: usize,
+        subs: impl IntoIterator<Item=impl Into<SubstatRoll>>,
+    ) -> Result<Self, InvalidRelicError> {
+        let mut relic = Self::new(rarity, slot, main).copy_with_new_subs(subs);
+        relic.level = level;
+        relic.validate()?;
+        Ok(relic)
+    }
+
+    /// Checks that the relic could have dropped and been leveled like this
+    pub fn validate(&self) -> Result<(), InvalidRelicError> {
+        self.validate_kind()?;
+
+        let max = self.max_level();
+        if self.level > max {
+            return Err(InvalidRelicError::Level { level: self.level, max });
+        }
+
+        for stat in self.subs.iter().map(|r| r.stat).unique() {
+            if stat == self.main {
+                return Err(InvalidRelicError::SubstatIsMain(stat));
+            }
+            if stat.substat_probability_weight() == 0 {
+                return Err(InvalidRelicError::NotASubstat(stat));
+            }
+        }
+
+        let upgrades = self.level / 3;
+        let (min, max) = (self.rarity - 2 + upgrades, self.rarity - 1 + upgrades);
+        let rolls = self.subs.len();
+        if !(min..=max).contains(&rolls) {
+            return Err(InvalidRelicError::Rolls { rolls, level: self.level, min, max });
+        }
+
+        // every roll opens a new line until there are four
+        let lines = self.subs.iter().map(|r| r.stat).unique().count();
+        let expected = rolls.min(4);
+        if lines != expected {
+            return Err(InvalidRelicError::Lines { lines, expected });
+        }
+        Ok(())
+    }
+
+    /// Checks only the rarity, slot, main stat and set, for relics that stand in for every relic
+    /// of their kind and leave the substats empty
+    pub fn validate_kind(&self) -> Result<(), InvalidRelicError> {
+        if !(2..=5).contains(&self.rarity) {
+            return Err(InvalidRelicError::Rarity(self.rarity));
+        }
+        if GameTables::with(|tables| tables.p_main_stat(self.slot, self.main)) == 0.0 {
+            return Err(InvalidRelicError::MainStat { slot: self.slot, main: self.main });
+        }
+        if let Some(set) = self.set.filter(|set| !set.slots().contains(&self.slot)) {
+            return Err(InvalidRelicError::SetSlot { set, slot: self.slot });
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use RelicStat::*;
+
+    #[test]
+    fn validate() {
+        assert!(Relic::try_new(5, RelicSlot::Head, Hp, 0, [CritRate, CritDmg, Spd]).is_ok());
+        assert!(Relic::try_new(5, RelicSlot::Head, Hp, 15, [CritRate, CritDmg, Spd, Atk, CritRate, CritRate, CritDmg, Spd, Spd]).is_ok());
+        assert!(Relic::try_new(3, RelicSlot::Orb, IceDmgBoost, 6, [Atk, Def, Spd]).is_ok());
+        assert!(Relic::new(5, RelicSlot::Rope, EnergyRegenRate).validate_kind().is_ok());
+
+        let error = |rarity, slot, main, level, subs: &[RelicStat]| {
+            Relic::try_new(rarity, slot, main, level, subs.iter().copied()).unwrap_err()
+        };
+        assert_eq!(InvalidRelicError::Rarity(0), error(0, RelicSlot::Head, Hp, 0, &[]));
+        assert_eq!(
+            InvalidRelicError::MainStat { slot: RelicSlot::Hands, main: Hp },
+            error(5, RelicSlot::Hands, Hp, 0, &[Atk, Def, Spd]),
+        );
+        assert_eq!(
+            InvalidRelicError::MainStat { slot: RelicSlot::Body, main: Spd },
+            error(5, RelicSlot::Body, Spd, 0, &[Atk, Def, Hp]),
+        );
+        assert_eq!(
+            InvalidRelicError::SetSlot { set: RelicSet::Glamoth, slot: RelicSlot::Head },
+            Relic::new(5, RelicSlot::Head, Hp).set(RelicSet::Glamoth).validate_kind().unwrap_err(),
+        );
+        assert!(Relic::new(5, RelicSlot::Rope, EnergyRegenRate).set(RelicSet::Glamoth).validate_kind().is_ok());
+        assert_eq!(InvalidRelicError::Level { level: 18, max: 15 }, error(5, RelicSlot::Head, Hp, 18, &[]));
+        assert_eq!(InvalidRelicError::SubstatIsMain(Hp), error(5, RelicSlot::Head, Hp, 0, &[Atk, Hp, Spd]));
+        assert_eq!(InvalidRelicError::NotASubstat(FireDmgBoost), error(5, RelicSlot::Head, Hp, 0, &[Atk, FireDmgBoost, Spd]));
+        assert_eq!(
+            InvalidRelicError::Lines { lines: 5, expected: 4 },
+            error(5, RelicSlot::Head, Hp, 3, &[Atk, Def, Spd, CritRate, CritDmg]),
+        );
+        assert_eq!(
+            InvalidRelicError::Lines { lines: 2, expected: 3 },
+            error(5, RelicSlot::Head, Hp, 0, &[Atk, Atk, Spd]),
+        );
+        assert_eq!(
+            InvalidRelicError::Rolls { rolls: 6, level: 3, min: 4, max: 5 },
+            error(5, RelicSlot::Head, Hp, 3, &[Atk, Def, Spd, CritRate, Atk, Atk]),
+        );
+        assert_eq!("HP can't be the main stat of Hands", error(5, RelicSlot::Hands, Hp, 0, &[]).to_string());
+
+        // the probability APIs refuse relics that can't drop
+        let hp_hands = Relic::new(5, RelicSlot::Hands, Hp);
+        let invalid = InvalidRelicError::MainStat { slot: RelicSlot::Hands, main: Hp };
+        assert_eq!(Err(invalid.clone()), hp_hands.p_main());
+        assert_eq!(Err(invalid.clone()), hp_hands.p_main_stat());
+        assert_eq!(Err(invalid.clone()), hp_hands.filtered_p_sub(|_| true));
+        assert_eq!(Some(invalid), hp_hands.score_distribution(&crate::LinearScore::crit_value()).err());
+        assert!(Relic::new(5, RelicSlot::Head, Hp).copy_with_new_subs([Atk, Def, Spd]).p_sub().is_err());
+        let rolled = Relic::try_new(5, RelicSlot::Head, Hp, 15, [CritRate, CritDmg, Spd, Atk, CritRate, CritRate, CritDmg, Spd, Spd]).unwrap();
+        assert!(rolled.p_sub().unwrap() > 0.0);
+    }
+}